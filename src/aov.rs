@@ -0,0 +1,163 @@
+use types::*;
+use crate::Canvas;
+use crate::RenderConfig;
+use crate::CancelToken;
+use crate::render_pixels;
+
+use std::path::Path;
+use std::error::Error;
+
+// Arbitrary output values, extra images about what the camera ray through each pixel hit, for
+// compositing and debugging. Pixels where the ray misses have no value.
+#[derive(Debug, Clone)]
+pub struct AovBuffers {
+    pub width: usize,
+    pub height: usize,
+    // Distance along the ray to the hit
+    pub depth: Vec<Option<f64>>,
+    // World space normal, facing the camera
+    pub normal: Vec<Option<Vector4D>>,
+    // Surface color without any lighting, with patterns
+    pub albedo: Vec<Option<Color>>,
+    // Index of the object in World::objects, for shapes inside groups the group's
+    pub object_id: Vec<Option<usize>>,
+    // Objects with the same material share an id, see material_ids
+    pub material_id: Vec<Option<usize>>,
+}
+
+impl AovBuffers {
+    pub fn new(width: usize, height: usize) -> AovBuffers {
+        let size = width * height;
+        AovBuffers {
+            width,
+            height,
+            depth: vec![None; size],
+            normal: vec![None; size],
+            albedo: vec![None; size],
+            object_id: vec![None; size],
+            material_id: vec![None; size],
+        }
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    // Fill in the pixel from the hit the camera ray through it shaded, material_ids is from
+    // material_ids for the world the hit is in
+    pub fn fill_pixel(&mut self, material_ids: &[usize], sc: &ShadeComputation, x: usize, y: usize) {
+        let material = sc.obj.get_material();
+        let i = self.index(x, y);
+        self.depth[i] = Some(sc.t * sc.eyev.norm());
+        self.normal[i] = Some(sc.normalv);
+        self.albedo[i] = Some(material.color_at_object(sc.obj.as_intersectable(), sc.point));
+        self.object_id[i] = sc.object;
+        self.material_id[i] = sc.object.and_then(|id| material_ids.get(id).cloned());
+    }
+
+    fn to_canvas<F: Fn(usize) -> Option<Color>>(&self, color: F) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if let Some(c) = color(self.index(x, y)) {
+                    canvas.set_pixel(x, y, &c);
+                }
+            }
+        }
+        canvas
+    }
+
+    // White up close fading to black at the farthest hit
+    pub fn depth_canvas(&self) -> Canvas {
+        let max_depth = self.depth.iter().flatten().cloned().fold(0.0, f64::max);
+        self.to_canvas(|i| self.depth[i].map(|d| {
+            let v = if max_depth > 0.0 { 1.0 - d / max_depth } else { 1.0 };
+            Color::new(v, v, v)
+        }))
+    }
+
+    // x, y, z of the normal as red, green and blue, scaled from -1..1 to 0..1
+    pub fn normal_canvas(&self) -> Canvas {
+        self.to_canvas(|i| self.normal[i].map(|n| {
+            Color::new((n.x + 1.0) / 2.0, (n.y + 1.0) / 2.0, (n.z + 1.0) / 2.0)
+        }))
+    }
+
+    pub fn albedo_canvas(&self) -> Canvas {
+        self.to_canvas(|i| self.albedo[i])
+    }
+
+    pub fn object_id_canvas(&self) -> Canvas {
+        self.to_canvas(|i| self.object_id[i].map(id_color))
+    }
+
+    pub fn material_id_canvas(&self) -> Canvas {
+        self.to_canvas(|i| self.material_id[i].map(id_color))
+    }
+
+    // Writes <prefix>_depth.ppm, <prefix>_normal.ppm, <prefix>_albedo.ppm,
+    // <prefix>_object_id.ppm and <prefix>_material_id.ppm
+    pub fn write_ppms<P: AsRef<Path>>(&self, prefix: P) -> Result<(), Box<dyn Error>> {
+        let prefix = prefix.as_ref().to_string_lossy().into_owned();
+        self.depth_canvas().write_ppm(format!("{}_depth.ppm", prefix))?;
+        self.normal_canvas().write_ppm(format!("{}_normal.ppm", prefix))?;
+        self.albedo_canvas().write_ppm(format!("{}_albedo.ppm", prefix))?;
+        self.object_id_canvas().write_ppm(format!("{}_object_id.ppm", prefix))?;
+        self.material_id_canvas().write_ppm(format!("{}_material_id.ppm", prefix))?;
+        Ok(())
+    }
+}
+
+// A bright color that's easy to tell apart from the colors of nearby ids
+pub fn id_color(id: usize) -> Color {
+    // step around the hue circle by the golden ratio
+    let hue = (id as f64 * 0.618_033_988_75).fract() * 6.0;
+    let f = hue.fract();
+    match hue as usize {
+        0 => Color::new(1.0, f, 0.0),
+        1 => Color::new(1.0 - f, 1.0, 0.0),
+        2 => Color::new(0.0, 1.0, f),
+        3 => Color::new(0.0, 1.0 - f, 1.0),
+        4 => Color::new(f, 0.0, 1.0),
+        _ => Color::new(1.0, 0.0, 1.0 - f),
+    }
+}
+
+// For each object the index of the first object with the same material. Patterns and bumps
+// can't be compared, so materials with either are only the same as themselves.
+pub fn material_ids(world: &World) -> Vec<usize> {
+    let materials: Vec<Material> = world.objects.iter().map(|o| o.get_material()).collect();
+    (0..materials.len())
+        .map(|i| (0..i).find(|&j| same_material(&materials[j], &materials[i])).unwrap_or(i))
+        .collect()
+}
+
+fn same_material(a: &Material, b: &Material) -> bool {
+    // every field is named so a new one can't be forgotten here
+    let Material { color, ambient, diffuse, specular, shininess, shading, ref pattern, ref bump, no_cast_shadow,
+                   emissive, reflective, refractive_index, dispersion, transparency, absorption, absorption_density,
+                   reflect_roughness, refract_roughness, glossy_samples } = *a;
+    pattern.is_none() && bump.is_none() && b.pattern.is_none() && b.bump.is_none() &&
+        color == b.color && ambient == b.ambient && diffuse == b.diffuse && specular == b.specular &&
+        shininess == b.shininess && shading == b.shading && no_cast_shadow == b.no_cast_shadow &&
+        emissive == b.emissive && reflective == b.reflective && refractive_index == b.refractive_index &&
+        dispersion == b.dispersion && transparency == b.transparency && absorption == b.absorption &&
+        absorption_density == b.absorption_density && reflect_roughness == b.reflect_roughness &&
+        refract_roughness == b.refract_roughness && glossy_samples == b.glossy_samples
+}
+
+// Renders config's region into canvas like render_with_config and returns the output values of
+// the first hit of the first sample of each pixel, taken from the render itself.
+pub fn render_with_aovs(camera: &Camera, world: &World, canvas: &mut Canvas, config: &RenderConfig) -> AovBuffers {
+    let ids = material_ids(world);
+    let mut aovs = AovBuffers::new(camera.hsize_px, camera.vsize_px);
+    let (_, stats) = render_pixels(camera, world, canvas, config, &CancelToken::new(), |_| (), |x, y, hit| {
+        if let Some(sc) = hit {
+            aovs.fill_pixel(&ids, sc, x, y);
+        }
+    });
+    if let Some(stats) = stats {
+        print!("{}", stats);
+    }
+    aovs
+}
@@ -6,6 +6,7 @@ use std::io::prelude::*;
 use std::io::BufWriter;
 use std::error::Error;
 
+#[derive(Clone)]
 pub struct Canvas {
     pub width: usize,
     pub height: usize,
@@ -48,6 +49,69 @@ impl Canvas {
     }
     
 
+    pub fn from_ppm<P: AsRef<Path>>(filepath: P) -> Result<Canvas, Box<dyn Error>> {
+        let bytes = std::fs::read(filepath)?;
+        Canvas::from_ppm_bytes(&bytes)
+    }
+
+    pub fn from_ppm_str(ppm: &str) -> Result<Canvas, Box<dyn Error>> {
+        Canvas::from_ppm_bytes(ppm.as_bytes())
+    }
+
+    // Reads both the plain (P3) and binary (P6) flavors of PPM. Colors are scaled by the maximum
+    // color value in the header so they end up in the range 0.0 - 1.0.
+    pub fn from_ppm_bytes(bytes: &[u8]) -> Result<Canvas, Box<dyn Error>> {
+        let mut reader = PpmReader { bytes, pos: 0 };
+        let magic = reader.next_token().ok_or("ppm is missing the magic number")?;
+        if magic != "P3" && magic != "P6" {
+            return Err(format!("unsupported ppm magic number: {}", magic).into());
+        }
+        let width: usize = reader.next_token().ok_or("ppm is missing the width")?.parse()?;
+        let height: usize = reader.next_token().ok_or("ppm is missing the height")?.parse()?;
+        if width == 0 || height == 0 {
+            return Err(format!("ppm size {}x{} has no pixels", width, height).into());
+        }
+        let max_value: usize = reader.next_token().ok_or("ppm is missing the maximum color value")?.parse()?;
+        if max_value == 0 || max_value > 65535 {
+            return Err(format!("invalid ppm maximum color value: {}", max_value).into());
+        }
+
+        // Every sample takes at least a byte, so a header asking for more pixels than the file
+        // could hold is broken and mustn't be trusted with the allocation
+        let sample_count = width.checked_mul(height).and_then(|pixels| pixels.checked_mul(3))
+            .ok_or_else(|| format!("ppm size {}x{} is too large", width, height))?;
+        if sample_count > bytes.len() {
+            return Err(format!("ppm size {}x{} is larger than the file", width, height).into());
+        }
+        let mut samples = Vec::with_capacity(sample_count);
+        if magic == "P3" {
+            for _ in 0..sample_count {
+                let sample: usize = reader.next_token().ok_or("ppm has too few pixels")?.parse()?;
+                samples.push(sample);
+            }
+        } else {
+            // A single whitespace byte separates the header from the binary pixels
+            let start = reader.pos + 1;
+            let bytes_per_sample = if max_value < 256 { 1 } else { 2 };
+            let end = start + sample_count * bytes_per_sample;
+            if end > bytes.len() {
+                return Err("ppm has too few pixels".into());
+            }
+            for chunk in bytes[start..end].chunks(bytes_per_sample) {
+                let sample = chunk.iter().fold(0, |acc, b| (acc << 8) | *b as usize);
+                samples.push(sample);
+            }
+        }
+
+        let mut canvas = Canvas::new(width, height);
+        let scale = max_value as f64;
+        for (i, rgb) in samples.chunks(3).enumerate() {
+            let color = Color::new(rgb[0] as f64 / scale, rgb[1] as f64 / scale, rgb[2] as f64 / scale);
+            canvas.set_pixel(i % width, i / width, &color);
+        }
+        Ok(canvas)
+    }
+
     pub fn write_ppm<P: AsRef<Path>>(&self, filepath: P) -> Result<(), Box<dyn Error>> {
         let mut f = OpenOptions::new().write(true).create(true).open(filepath)?;
         f.write_all(&self.write_ppm_str().as_bytes())?;
@@ -103,3 +167,35 @@ impl Canvas {
         String::from_utf8(bw.into_inner().unwrap()).unwrap()
     }
 }
+
+// Splits the header (and P3 pixels) of a ppm into whitespace separated tokens, skipping comments
+struct PpmReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PpmReader<'a> {
+    fn next_token(&mut self) -> Option<String> {
+        loop {
+            while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            if self.pos < self.bytes.len() && self.bytes[self.pos] == b'#' {
+                while self.pos < self.bytes.len() && self.bytes[self.pos] != b'\n' {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+        let start = self.pos;
+        while self.pos < self.bytes.len() && !self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            None
+        } else {
+            Some(String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned())
+        }
+    }
+}
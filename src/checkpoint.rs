@@ -0,0 +1,137 @@
+use types::*;
+use crate::Canvas;
+use crate::RenderConfig;
+use crate::RenderRegion;
+use crate::CancelToken;
+use crate::RenderStats;
+use crate::PixelCounter;
+use crate::render_pixel;
+
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::io::prelude::*;
+use std::error::Error;
+
+// Renders config's region tile by tile, appending every finished tile to the checkpoint file. If
+// the checkpoint file is already there from an interrupted render of the same image, the tiles
+// in it are put back into canvas and only the rest are rendered. Returns false when cancel
+// stopped the render, the checkpoint is kept to resume from, and true when every tile is done,
+// the checkpoint is then removed. With config.stats the summary of the tiles rendered this time
+// is printed at the end.
+//
+// The checkpoint is text, a header line with the image size, region, tile size, integrator,
+// samples per pixel, max depth and a hash of the scene, then for every tile its index, the full
+// precision colors of its pixels a row per line, and "end". A checkpoint for another render is
+// an error. A tile that was cut off while it was written is rendered again, and a header that was
+// cut off starts the render over.
+pub fn render_with_checkpoint<P: AsRef<Path>>(camera: &Camera, world: &World, canvas: &mut Canvas, config: &RenderConfig,
+                                              tile_size: usize, checkpoint: P, cancel: &CancelToken) -> Result<bool, Box<dyn Error>> {
+    let checkpoint = checkpoint.as_ref();
+    let region = config.pixels(camera);
+    let tiles = region.tiles(tile_size);
+    let header = format!("checkpoint {} {} {} {} {} {} {} {:?} {} {} {:016x}", camera.hsize_px, camera.vsize_px,
+                         region.x, region.y, region.width, region.height, tile_size.max(1),
+                         config.integrator, config.samples_per_pixel, config.max_depth, scene_hash(camera, world));
+
+    let contents = if checkpoint.exists() { std::fs::read_to_string(checkpoint)? } else { String::new() };
+    // without the newline the header itself was cut off, no tile was written yet
+    let done = if contents.contains('\n') {
+        // anything after the last end is a tile that was cut off while it was written, it's
+        // dropped so the tiles appended next follow the complete ones
+        let complete = match contents.rfind("\nend\n") {
+            Some(end) => &contents[..end + 5],
+            None => &contents[..contents.find('\n').map_or(contents.len(), |end| end + 1)],
+        };
+        let done = load_checkpoint(canvas, complete, &header, &tiles)?;
+        OpenOptions::new().write(true).open(checkpoint)?.set_len(complete.len() as u64)?;
+        done
+    } else {
+        let mut f = OpenOptions::new().write(true).create(true).truncate(true).open(checkpoint)?;
+        writeln!(f, "{}", header)?;
+        HashSet::new()
+    };
+
+    let mut stats = RenderStats::new(camera.hsize_px, camera.vsize_px);
+    let mut counter = if config.stats { Some(PixelCounter::start()) } else { None };
+    let mut f = OpenOptions::new().append(true).open(checkpoint)?;
+    for (i, tile) in tiles.iter().enumerate() {
+        if done.contains(&i) {
+            continue;
+        }
+        if cancel.is_cancelled() {
+            if config.stats {
+                print!("{}", stats);
+            }
+            return Ok(false);
+        }
+        let mut entry = format!("tile {}\n", i);
+        for y in tile.y..tile.y + tile.height {
+            let mut row = vec![];
+            for x in tile.x..tile.x + tile.width {
+                let color = render_pixel(camera, world, config, x, y);
+                canvas.set_pixel(x, y, &color);
+                if let Some(ref mut counter) = counter {
+                    counter.pixel_done(&mut stats, x, y);
+                }
+                row.push(format!("{} {} {}", color.red, color.green, color.blue));
+            }
+            entry.push_str(&row.join(" "));
+            entry.push('\n');
+        }
+        entry.push_str("end\n");
+        // the whole tile at once so an interrupted render leaves at most one partial tile
+        f.write_all(entry.as_bytes())?;
+        f.flush()?;
+    }
+    drop(f);
+    std::fs::remove_file(checkpoint)?;
+    if config.stats {
+        print!("{}", stats);
+    }
+    Ok(true)
+}
+
+// FNV-1a hash of the camera and everything in the world that changes the colors, so editing the
+// scene doesn't resume from the old render. The Debug output hashes the same in every run, unlike
+// the std hashers.
+fn scene_hash(camera: &Camera, world: &World) -> u64 {
+    // the photons themselves are many and follow from the scene, their settings are enough
+    let photons = world.photon_map.as_ref().map(|map| (map.photons.len(), map.gather_count, map.max_radius));
+    let scene = format!("{} {} {} {:?} {:?} {:?} {:?} {} {:?} {:?} {:?} {:?}", camera.hsize_px, camera.vsize_px,
+                        camera.fov, camera.transform, world.light_source, world.objects, world.background,
+                        world.area_light_samples, photons, world.fog, world.volumes, world.ambient_occlusion);
+    scene.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+// Puts the tiles in the checkpoint, without any cut off tile at the end, into canvas and returns their indices
+fn load_checkpoint(canvas: &mut Canvas, contents: &str, header: &str, tiles: &[RenderRegion]) -> Result<HashSet<usize>, Box<dyn Error>> {
+    let mut lines = contents.lines();
+    let found = lines.next().unwrap_or("");
+    if found != header {
+        return Err(format!("checkpoint is for a different render: '{}', expected '{}'", found, header).into());
+    }
+    let mut done = HashSet::new();
+    while let Some(line) = lines.next() {
+        let index: usize = match line.strip_prefix("tile ") {
+            Some(index) => index.parse()?,
+            None => return Err(format!("unexpected line in checkpoint: '{}'", line).into()),
+        };
+        let tile = tiles.get(index).ok_or_else(|| format!("checkpoint has tile {} but there are {}", index, tiles.len()))?;
+        for dy in 0..tile.height {
+            let row = lines.next().ok_or("checkpoint ends in the middle of a tile")?;
+            let values = row.split_whitespace().map(|v| v.parse()).collect::<Result<Vec<f64>, _>>()?;
+            if values.len() != tile.width * 3 {
+                return Err(format!("checkpoint tile {} has a row of {} values, expected {}", index, values.len(), tile.width * 3).into());
+            }
+            for (dx, rgb) in values.chunks(3).enumerate() {
+                canvas.set_pixel(tile.x + dx, tile.y + dy, &Color::new(rgb[0], rgb[1], rgb[2]));
+            }
+        }
+        if lines.next() != Some("end") {
+            return Err(format!("checkpoint tile {} has too many rows", index).into());
+        }
+        done.insert(index);
+    }
+    Ok(done)
+}
@@ -0,0 +1,121 @@
+use types::*;
+use crate::Canvas;
+use crate::AovBuffers;
+
+use std::error::Error;
+
+// Settings for Canvas::denoise. The sigmas are how big a difference is before a neighboring
+// pixel stops being averaged in, smaller keeps more detail and removes less noise. Sigmas of
+// zero or less are taken as utils::EPSILON.
+#[derive(Debug, Clone, Copy)]
+pub struct DenoiseSettings {
+    // Pixels out to radius in every direction are averaged
+    pub radius: usize,
+    // In pixels
+    pub sigma_spatial: f64,
+    pub sigma_color: f64,
+    // Only used with AOV buffers. The normal difference is the distance between the two unit
+    // normals, from 0.0 for the same direction to 2.0 for opposite ones.
+    pub sigma_normal: f64,
+    pub sigma_albedo: f64,
+    // Relative to the depth of the center pixel
+    pub sigma_depth: f64,
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> Self {
+        DenoiseSettings {
+            radius: 3,
+            sigma_spatial: 2.0,
+            sigma_color: 0.3,
+            sigma_normal: 0.2,
+            sigma_albedo: 0.1,
+            sigma_depth: 0.05,
+        }
+    }
+}
+
+fn color_distance2(a: Color, b: Color) -> f64 {
+    let d = a - b;
+    d.red * d.red + d.green * d.green + d.blue * d.blue
+}
+
+fn vector_distance2(a: Vector4D, b: Vector4D) -> f64 {
+    let d = a - b;
+    d.x * d.x + d.y * d.y + d.z * d.z
+}
+
+// The 2 sigma^2 of a gaussian exp(-d^2 / (2 sigma^2))
+fn gaussian_scale(sigma: f64) -> f64 {
+    // A zero, negative or NaN sigma turns into the smallest one, which gives neighbors that
+    // differ at all no weight instead of dividing by zero in the weights
+    let sigma = sigma.max(utils::EPSILON);
+    2.0 * sigma * sigma
+}
+
+impl Canvas {
+    // A joint bilateral filter that removes sampling noise but keeps edges. Every pixel is
+    // averaged with its neighbors, weighted by how close they are and how similar their color
+    // is. With AOV buffers from the same camera the normals, albedo and depth are used too, so
+    // edges between objects and texture details stay sharp while the noise in the lighting is
+    // smoothed out. The AOV buffers have to be the size of the canvas.
+    pub fn denoise(&self, guide: Option<&AovBuffers>, settings: &DenoiseSettings) -> Result<Canvas, Box<dyn Error>> {
+        if let Some(aovs) = guide {
+            let size = self.width * self.height;
+            let lengths = [aovs.normal.len(), aovs.albedo.len(), aovs.depth.len()];
+            if aovs.width != self.width || aovs.height != self.height || lengths.iter().any(|&l| l != size) {
+                return Err(format!("AOV buffers are {}x{} but the canvas is {}x{}",
+                                   aovs.width, aovs.height, self.width, self.height).into());
+            }
+        }
+        let radius = settings.radius as i64;
+        let spatial = gaussian_scale(settings.sigma_spatial);
+        let color = gaussian_scale(settings.sigma_color);
+        let normal = gaussian_scale(settings.sigma_normal);
+        let albedo = gaussian_scale(settings.sigma_albedo);
+        let depth = gaussian_scale(settings.sigma_depth);
+
+        let mut denoised = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let center = self.get_pixel(x, y);
+                let ci = y * self.width + x;
+                let mut total = Color::BLACK;
+                let mut total_weight = 0.0;
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                        if nx < 0 || ny < 0 || nx >= self.width as i64 || ny >= self.height as i64 {
+                            continue;
+                        }
+                        let (nx, ny) = (nx as usize, ny as usize);
+                        let neighbor = self.get_pixel(nx, ny);
+                        let mut exponent = (dx * dx + dy * dy) as f64 / spatial +
+                            color_distance2(center, neighbor) / color;
+                        if let Some(aovs) = guide {
+                            let ni = ny * self.width + nx;
+                            match (aovs.normal[ci], aovs.normal[ni]) {
+                                (Some(a), Some(b)) => exponent += vector_distance2(a, b) / normal,
+                                (None, None) => (),
+                                // don't mix the background into objects or the other way around
+                                _ => continue,
+                            }
+                            if let (Some(a), Some(b)) = (aovs.albedo[ci], aovs.albedo[ni]) {
+                                exponent += color_distance2(a, b) / albedo;
+                            }
+                            if let (Some(a), Some(b)) = (aovs.depth[ci], aovs.depth[ni]) {
+                                let relative = (a - b) / a.max(utils::EPSILON);
+                                exponent += relative * relative / depth;
+                            }
+                        }
+                        let weight = (-exponent).exp();
+                        total = total + neighbor * weight;
+                        total_weight += weight;
+                    }
+                }
+                denoised.set_pixel(x, y, &(total * (1.0 / total_weight)));
+            }
+        }
+        Ok(denoised)
+    }
+}
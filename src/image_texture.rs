@@ -0,0 +1,146 @@
+use types::*;
+use crate::Canvas;
+
+use std::path::Path;
+use std::error::Error;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureFilter {
+    Nearest,
+    Bilinear,
+}
+
+// What happens when a texel lookup falls off the edge of the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureWrap {
+    Repeat,
+    Clamp,
+}
+
+// A uv pattern that samples an image. Put it on a shape with a TextureMapPattern.
+// The images are shared between clones since materials get cloned a lot.
+#[derive(Clone)]
+pub struct ImageTexture {
+    // Level 0 is the full size image, every level after is half the size of the previous one.
+    pub mip_levels: Arc<Vec<Canvas>>,
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+    // Which mip level to sample, fractional levels blend between the two nearest levels. Use a
+    // higher level for surfaces far away from the camera so the image doesn't alias. Patterns
+    // don't know how far away the point they're looking up is, so the level isn't picked for
+    // you, set it for the texture with level_for_footprint.
+    pub mip_level: f64,
+}
+
+impl std::fmt::Debug for ImageTexture {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(fmt, "ImageTexture (Size: {}x{}, Mip levels: {}, Filter: {:?}, Wrap: {:?}, Mip level: {})",
+               self.mip_levels[0].width, self.mip_levels[0].height, self.mip_levels.len(),
+               self.filter, self.wrap, self.mip_level)
+    }
+}
+
+impl ImageTexture {
+    pub fn new(canvas: Canvas) -> ImageTexture {
+        ImageTexture {
+            mip_levels: Arc::new(vec![canvas]),
+            filter: TextureFilter::Bilinear,
+            wrap: TextureWrap::Repeat,
+            mip_level: 0.0,
+        }
+    }
+
+    pub fn from_ppm<P: AsRef<Path>>(filepath: P) -> Result<ImageTexture, Box<dyn Error>> {
+        Ok(ImageTexture::new(Canvas::from_ppm(filepath)?))
+    }
+
+    // Build the mip chain by averaging 2x2 blocks of the previous level down to a 1x1 image.
+    pub fn generate_mipmaps(&mut self) {
+        let mut levels = vec![];
+        let mut current = self.mip_levels[0].clone();
+        while current.width > 1 || current.height > 1 {
+            let next = downsample(&current);
+            levels.push(current);
+            current = next;
+        }
+        levels.push(current);
+        self.mip_levels = Arc::new(levels);
+    }
+
+    // The mip level where a texel is about the size of a pixel, for a surface where one pixel
+    // covers uv_per_pixel of the texture, for example the pixel size at the distance of the
+    // surface divided by the size of the surface the texture is stretched over.
+    pub fn level_for_footprint(&self, uv_per_pixel: f64) -> f64 {
+        let image = &self.mip_levels[0];
+        let texels = uv_per_pixel * image.width.max(image.height) as f64;
+        if texels <= 1.0 {
+            return 0.0;
+        }
+        texels.log2().min((self.mip_levels.len() - 1) as f64)
+    }
+
+    fn texel(&self, canvas: &Canvas, x: i64, y: i64) -> Color {
+        let (x, y) = match self.wrap {
+            TextureWrap::Repeat => (x.rem_euclid(canvas.width as i64), y.rem_euclid(canvas.height as i64)),
+            TextureWrap::Clamp => (x.max(0).min(canvas.width as i64 - 1), y.max(0).min(canvas.height as i64 - 1)),
+        };
+        canvas.get_pixel(x as usize, y as usize)
+    }
+
+    fn sample_level(&self, level: usize, u: f64, v: f64) -> Color {
+        let canvas = &self.mip_levels[level];
+        // v goes up but the rows of the image go down, and texel centers are at .5
+        let x = u * canvas.width as f64 - 0.5;
+        let y = (1.0 - v) * canvas.height as f64 - 0.5;
+        match self.filter {
+            TextureFilter::Nearest => {
+                self.texel(canvas, x.round() as i64, y.round() as i64)
+            },
+            TextureFilter::Bilinear => {
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let fx = x - x0;
+                let fy = y - y0;
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = self.texel(canvas, x0, y0) * (1.0 - fx) + self.texel(canvas, x0 + 1, y0) * fx;
+                let bottom = self.texel(canvas, x0, y0 + 1) * (1.0 - fx) + self.texel(canvas, x0 + 1, y0 + 1) * fx;
+                top * (1.0 - fy) + bottom * fy
+            },
+        }
+    }
+}
+
+impl UvPatternable for ImageTexture {
+    fn uv_pattern_at(&self, u: f64, v: f64) -> Color {
+        let max_level = (self.mip_levels.len() - 1) as f64;
+        let level = self.mip_level.max(0.0).min(max_level);
+        let lower = level.floor();
+        let fraction = level - lower;
+        let color = self.sample_level(lower as usize, u, v);
+        if fraction > 0.0 {
+            color * (1.0 - fraction) + self.sample_level(lower as usize + 1, u, v) * fraction
+        } else {
+            color
+        }
+    }
+}
+
+fn downsample(canvas: &Canvas) -> Canvas {
+    let width = (canvas.width / 2).max(1);
+    let height = (canvas.height / 2).max(1);
+    let mut smaller = Canvas::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            // Odd sized images reuse the last row/column
+            let x0 = (2 * x).min(canvas.width - 1);
+            let x1 = (2 * x + 1).min(canvas.width - 1);
+            let y0 = (2 * y).min(canvas.height - 1);
+            let y1 = (2 * y + 1).min(canvas.height - 1);
+            let sum = canvas.get_pixel(x0, y0) + canvas.get_pixel(x1, y0) +
+                canvas.get_pixel(x0, y1) + canvas.get_pixel(x1, y1);
+            smaller.set_pixel(x, y, &(sum * 0.25));
+        }
+    }
+    smaller
+}
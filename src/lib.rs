@@ -4,3 +4,20 @@ pub use canvas::*;
 mod render;
 pub use render::*;
 
+mod image_texture;
+pub use image_texture::*;
+
+mod aov;
+pub use aov::*;
+
+mod denoise;
+pub use denoise::*;
+
+mod progressive;
+pub use progressive::*;
+
+mod checkpoint;
+pub use checkpoint::*;
+
+mod render_stats;
+pub use render_stats::*;
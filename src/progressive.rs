@@ -0,0 +1,200 @@
+use types::*;
+use crate::Canvas;
+use crate::RenderConfig;
+use crate::RenderStats;
+use crate::PixelCounter;
+use crate::sample_color;
+
+use std::time::Duration;
+use std::time::Instant;
+
+// Settings for render_progressive. Passes are rendered until any of the limits that are set is
+// reached, with none set it never stops.
+#[derive(Debug, Clone)]
+pub struct ProgressiveConfig {
+    // samples_per_pixel is how many samples every pixel gets in each pass
+    pub render: RenderConfig,
+    // Stop once every pixel has this many samples
+    pub max_samples: Option<usize>,
+    // Stop after the first pass that ends past this
+    pub time_budget: Option<Duration>,
+    // Stop when the noise of the image, see SampleBuffer::noise, drops to this
+    pub noise_threshold: Option<f64>,
+}
+
+impl ProgressiveConfig {
+    pub fn new(render: RenderConfig) -> ProgressiveConfig {
+        ProgressiveConfig {
+            render,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for ProgressiveConfig {
+    fn default() -> Self {
+        ProgressiveConfig {
+            render: Default::default(),
+            max_samples: Some(64),
+            time_budget: None,
+            noise_threshold: None,
+        }
+    }
+}
+
+// Where a progressive render is at after a pass
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub passes: usize,
+    pub samples_per_pixel: usize,
+    pub elapsed: Duration,
+    pub noise: f64,
+}
+
+// The running totals of all the samples taken for each pixel, index is y * width + x
+#[derive(Debug, Clone)]
+pub struct SampleBuffer {
+    pub width: usize,
+    pub height: usize,
+    // Samples each pass rendered per pixel, added up
+    pub samples: usize,
+    // Samples taken in each pixel, pixels outside the region rendered have none
+    pub counts: Vec<usize>,
+    pub sum: Vec<Color>,
+    // Of the brightness of each sample, for the noise estimate
+    pub sum_brightness: Vec<f64>,
+    pub sum_squares: Vec<f64>,
+    // The work done for the samples of all the passes rendered with RenderConfig::stats set
+    pub stats: Option<RenderStats>,
+}
+
+fn brightness(c: Color) -> f64 {
+    0.2126 * c.red + 0.7152 * c.green + 0.0722 * c.blue
+}
+
+impl SampleBuffer {
+    pub fn new(width: usize, height: usize) -> SampleBuffer {
+        let size = width * height;
+        SampleBuffer {
+            width,
+            height,
+            samples: 0,
+            counts: vec![0; size],
+            sum: vec![Color::BLACK; size],
+            sum_brightness: vec![0.0; size],
+            sum_squares: vec![0.0; size],
+            stats: None,
+        }
+    }
+
+    // Adds config.samples_per_pixel samples spread randomly over every pixel in config's region.
+    // Every pass gets different samples, so the image keeps getting better.
+    pub fn render_pass(&mut self, camera: &Camera, world: &World, config: &RenderConfig) {
+        let samples = config.samples_per_pixel.max(1);
+        let region = config.pixels(camera);
+        let mut counter = if config.stats {
+            let (width, height) = (self.width, self.height);
+            self.stats.get_or_insert_with(|| RenderStats::new(width, height));
+            Some(PixelCounter::start())
+        } else {
+            None
+        };
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                let seed = (((y as u64) << 32) | x as u64)
+                    .wrapping_add((self.samples as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+                let mut sampler = Sampler::new(seed);
+                let i = y * self.width + x;
+                for _ in 0..samples {
+                    let ray = ray_for_canvas_point(camera, x as f64 + sampler.next_f64(), y as f64 + sampler.next_f64());
+                    let color = sample_color(world, config, ray, &mut sampler);
+                    let b = brightness(color);
+                    self.sum[i] = self.sum[i] + color;
+                    self.sum_brightness[i] += b;
+                    self.sum_squares[i] += b * b;
+                }
+                self.counts[i] += samples;
+                if let (Some(counter), Some(stats)) = (counter.as_mut(), self.stats.as_mut()) {
+                    counter.pixel_done(stats, x, y);
+                }
+            }
+        }
+        self.samples += samples;
+    }
+
+    // The average of the samples so far
+    pub fn estimate(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+        self.write_estimate(&mut canvas);
+        canvas
+    }
+
+    // Only the pixels with samples are written
+    pub fn write_estimate(&self, canvas: &mut Canvas) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = y * self.width + x;
+                if self.counts[i] > 0 {
+                    canvas.set_pixel(x, y, &(self.sum[i] * (1.0 / self.counts[i] as f64)));
+                }
+            }
+        }
+    }
+
+    // The standard error of the brightness of the pixels, averaged over the pixels with
+    // samples. It goes down with the square root of the number of samples, and is infinite
+    // until every pixel with samples has at least two.
+    pub fn noise(&self) -> f64 {
+        let mut total = 0.0;
+        let mut pixels = 0;
+        for i in 0..self.counts.len() {
+            let n = self.counts[i] as f64;
+            if self.counts[i] == 0 {
+                continue;
+            }
+            if self.counts[i] < 2 {
+                return f64::INFINITY;
+            }
+            let mean = self.sum_brightness[i] / n;
+            let variance = ((self.sum_squares[i] - n * mean * mean) / (n - 1.0)).max(0.0);
+            total += (variance / n).sqrt();
+            pixels += 1;
+        }
+        if pixels == 0 {
+            return f64::INFINITY;
+        }
+        total / pixels as f64
+    }
+}
+
+// Renders passes of samples into canvas until one of the limits in config is reached. After each
+// pass canvas holds the image so far and progress is called with it, for example to show it or
+// write it out. Returns the progress after the last pass. With config.render.stats the summary
+// of all the passes is printed at the end.
+pub fn render_progressive<F>(camera: &Camera, world: &World, canvas: &mut Canvas, config: &ProgressiveConfig, mut progress: F) -> Progress
+    where F: FnMut(&Canvas, &Progress) {
+    let start = Instant::now();
+    let mut buffer = SampleBuffer::new(camera.hsize_px, camera.vsize_px);
+    let mut passes = 0;
+    loop {
+        buffer.render_pass(camera, world, &config.render);
+        passes += 1;
+        buffer.write_estimate(canvas);
+        let status = Progress {
+            passes,
+            samples_per_pixel: buffer.samples,
+            elapsed: start.elapsed(),
+            noise: buffer.noise(),
+        };
+        progress(canvas, &status);
+        let done = config.max_samples.is_some_and(|max| status.samples_per_pixel >= max) ||
+            config.time_budget.is_some_and(|budget| status.elapsed >= budget) ||
+            config.noise_threshold.is_some_and(|threshold| status.noise <= threshold);
+        if done {
+            if let Some(ref stats) = buffer.stats {
+                print!("{}", stats);
+            }
+            return status;
+        }
+    }
+}
@@ -1,25 +1,299 @@
 use types::*;
 use crate::Canvas;
+use crate::RenderStats;
+use crate::PixelCounter;
+
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
 const REFLECT_RAYS: usize = 3;
-pub fn render(camera: &Camera, world: &World, canvas: &mut Canvas) {
-    for y in 0..(camera.vsize_px - 1) {
-        for x in 0..(camera.hsize_px - 1) {
-            let ray = ray_for_pixel(&camera, x, y); 
-            let color = color_at(&world, ray, 0);
-            canvas.set_pixel(x, y, &color);
+
+// How color_at is used to find the color of a pixel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Integrator {
+    // color_at, the book's recursive ray tracer
+    Whitted,
+    // path_trace, adds indirect light but needs many samples per pixel to not look noisy
+    PathTracing,
+    // Grayscale ambient occlusion of the first surface each ray hits, white where it's open and
+    // for rays that miss. Uses the world's AmbientOcclusion settings or the defaults.
+    AmbientOcclusion,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderConfig {
+    pub integrator: Integrator,
+    // How many times rays can bounce off or through objects
+    pub max_depth: usize,
+    // Rays per pixel, spread over the pixel and averaged. With one sample the ray goes through
+    // the center of the pixel.
+    pub samples_per_pixel: usize,
+    // Only render these pixels and leave the rest of the canvas as it is, None renders them all
+    pub region: Option<RenderRegion>,
+    // Count the rays and shape tests of every pixel, see RenderStats, and print a summary when
+    // the render is done. Counting slows the render down a little.
+    pub stats: bool,
+}
+
+impl RenderConfig {
+    pub fn new(integrator: Integrator) -> RenderConfig {
+        RenderConfig {
+            integrator,
+            ..Default::default()
+        }
+    }
+
+    // The pixels of camera's image to render
+    pub fn pixels(&self, camera: &Camera) -> RenderRegion {
+        let full = RenderRegion::full(camera);
+        self.region.map_or(full, |region| region.intersection(&full))
+    }
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        RenderConfig {
+            integrator: Integrator::Whitted,
+            max_depth: REFLECT_RAYS,
+            samples_per_pixel: 1,
+            region: None,
+            stats: false,
         }
     }
 }
 
-// TODO: We need to setup a RenderConfigurationObject have render take this object 
-// instead of what we're doing here making a copy of render with a non zero reflect rays
-// argument passed to color_at
+// A rectangle of pixels, x and y are the top left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderRegion {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl RenderRegion {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> RenderRegion {
+        RenderRegion {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // All of camera's image
+    pub fn full(camera: &Camera) -> RenderRegion {
+        RenderRegion::new(0, 0, camera.hsize_px, camera.vsize_px)
+    }
+
+    // The pixels in both regions, empty when they don't overlap
+    pub fn intersection(&self, other: &RenderRegion) -> RenderRegion {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        RenderRegion::new(x, y, right.saturating_sub(x), bottom.saturating_sub(y))
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    // Splits the region into tiles of up to tile_size by tile_size pixels, row by row
+    pub fn tiles(&self, tile_size: usize) -> Vec<RenderRegion> {
+        let tile_size = tile_size.max(1);
+        let mut tiles = vec![];
+        for y in (self.y..self.y + self.height).step_by(tile_size) {
+            for x in (self.x..self.x + self.width).step_by(tile_size) {
+                let tile = RenderRegion::new(x, y, tile_size, tile_size);
+                tiles.push(tile.intersection(self));
+            }
+        }
+        tiles
+    }
+}
+
+pub fn render(camera: &Camera, world: &World, canvas: &mut Canvas) {
+    let config = RenderConfig {
+        max_depth: 0,
+        ..Default::default()
+    };
+    render_with_config(camera, world, canvas, &config);
+}
+
 pub fn render_with_reflection(camera: &Camera, world: &World, canvas: &mut Canvas) {
-    for y in 0..(camera.vsize_px - 1) {
-        for x in 0..(camera.hsize_px - 1) {
-            let ray = ray_for_pixel(&camera, x, y); 
-            let color = color_at(&world, ray, REFLECT_RAYS);
+    render_with_config(camera, world, canvas, &Default::default());
+}
+
+pub fn render_with_config(camera: &Camera, world: &World, canvas: &mut Canvas, config: &RenderConfig) {
+    render_with_progress(camera, world, canvas, config, &CancelToken::new(), |_| ());
+}
+
+// Stops a render from another thread, or from the progress callback. Clones share the same
+// flag, so keep one and hand a clone to the render.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        Default::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+// How far along render_with_progress is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderProgress {
+    pub rows_done: usize,
+    pub total_rows: usize,
+    // Includes the pixels of a row that was cancelled part way through
+    pub pixels_done: usize,
+    // Rays traced through the world so far, camera rays and all the shadow, reflection and
+    // refraction rays they led to
+    pub rays_traced: usize,
+    pub elapsed: Duration,
+    // Time left if the remaining rows take as long as the ones done so far
+    pub eta: Duration,
+    pub cancelled: bool,
+}
+
+impl RenderProgress {
+    pub fn fraction_done(&self) -> f64 {
+        if self.total_rows == 0 {
+            1.0
+        } else {
+            self.rows_done as f64 / self.total_rows as f64
+        }
+    }
+}
+
+// Like render_with_config, calling progress after every row. When cancel is cancelled the render
+// stops after the pixel it's on, canvas keeps the pixels done so far and the returned progress
+// has cancelled set. With config.stats the summary is printed at the end, cancelled or not.
+pub fn render_with_progress<F>(camera: &Camera, world: &World, canvas: &mut Canvas, config: &RenderConfig, cancel: &CancelToken, progress: F) -> RenderProgress
+    where F: FnMut(&RenderProgress) {
+    let (status, stats) = render_pixels(camera, world, canvas, config, cancel, progress, |_, _, _| ());
+    if let Some(stats) = stats {
+        print!("{}", stats);
+    }
+    status
+}
+
+// render_with_progress, calling pixel_done with every pixel as it's finished and the first hit
+// of its first sample, for the renders that collect more than the image. With config.stats the
+// counts are returned too.
+pub(crate) fn render_pixels<F, P>(camera: &Camera, world: &World, canvas: &mut Canvas, config: &RenderConfig, cancel: &CancelToken,
+                                  mut progress: F, mut pixel_done: P) -> (RenderProgress, Option<RenderStats>)
+    where F: FnMut(&RenderProgress), P: FnMut(usize, usize, Option<&ShadeComputation>) {
+    let start = Instant::now();
+    let region = config.pixels(camera);
+    let rays_before = rays_traced();
+    let mut stats = if config.stats { Some(RenderStats::new(camera.hsize_px, camera.vsize_px)) } else { None };
+    let mut counter = if config.stats { Some(PixelCounter::start()) } else { None };
+    let mut status = RenderProgress {
+        rows_done: 0,
+        total_rows: region.height,
+        pixels_done: 0,
+        rays_traced: 0,
+        elapsed: Duration::from_secs(0),
+        eta: Duration::from_secs(0),
+        cancelled: false,
+    };
+    for y in region.y..region.y + region.height {
+        for x in region.x..region.x + region.width {
+            if cancel.is_cancelled() {
+                status.cancelled = true;
+                status.rays_traced = rays_traced().wrapping_sub(rays_before);
+                status.elapsed = start.elapsed();
+                return (status, stats);
+            }
+            let (color, hit) = render_pixel_and_hit(camera, world, config, x, y);
             canvas.set_pixel(x, y, &color);
+            pixel_done(x, y, hit.as_ref());
+            if let (Some(counter), Some(stats)) = (counter.as_mut(), stats.as_mut()) {
+                counter.pixel_done(stats, x, y);
+            }
+            status.pixels_done += 1;
         }
+        status.rows_done += 1;
+        status.rays_traced = rays_traced().wrapping_sub(rays_before);
+        status.elapsed = start.elapsed();
+        let rows_left = status.total_rows - status.rows_done;
+        status.eta = status.elapsed.mul_f64(rows_left as f64 / status.rows_done as f64);
+        progress(&status);
+    }
+    (status, stats)
+}
+
+pub fn render_pixel(camera: &Camera, world: &World, config: &RenderConfig, x: usize, y: usize) -> Color {
+    render_pixel_and_hit(camera, world, config, x, y).0
+}
+
+// Like render_pixel, also returns the first hit of the first sample
+pub(crate) fn render_pixel_and_hit(camera: &Camera, world: &World, config: &RenderConfig, x: usize, y: usize) -> (Color, Option<ShadeComputation>) {
+    let samples = config.samples_per_pixel.max(1);
+    let mut sampler = Sampler::new(((y as u64) << 32) | x as u64);
+    let mut total = Color::BLACK;
+    let mut first_hit = None;
+    for sample in 0..samples {
+        let ray = if samples == 1 {
+            ray_for_pixel(camera, x, y)
+        } else {
+            ray_for_canvas_point(camera, x as f64 + sampler.next_f64(), y as f64 + sampler.next_f64())
+        };
+        let (color, hit) = sample_color_and_hit(world, config, ray, &mut sampler);
+        total = total + color;
+        if sample == 0 {
+            first_hit = hit;
+        }
+    }
+    (total * (1.0 / samples as f64), first_hit)
+}
+
+// What happened to the rays of every sample of the pixel, to debug a pixel that doesn't look
+// right. Only the Whitted integrator is recorded, with the others this is empty.
+pub fn record_pixel(camera: &Camera, world: &World, config: &RenderConfig, x: usize, y: usize) -> Vec<RayRecord> {
+    start_recording_rays();
+    render_pixel(camera, world, config, x, y);
+    stop_recording_rays()
+}
+
+// The color seen along one camera ray with the config's integrator
+pub(crate) fn sample_color(world: &World, config: &RenderConfig, ray: Ray, sampler: &mut Sampler) -> Color {
+    sample_color_and_hit(world, config, ray, sampler).0
+}
+
+// Like sample_color, also returns the first hit along the ray
+fn sample_color_and_hit(world: &World, config: &RenderConfig, ray: Ray, sampler: &mut Sampler) -> (Color, Option<ShadeComputation>) {
+    match config.integrator {
+        Integrator::Whitted => color_and_hit_at(world, ray, config.max_depth),
+        Integrator::PathTracing => path_trace_and_hit(world, ray, config.max_depth, sampler),
+        Integrator::AmbientOcclusion => ambient_occlusion_at(world, ray),
+    }
+}
+
+fn ambient_occlusion_at(world: &World, ray: Ray) -> (Color, Option<ShadeComputation>) {
+    match ray.hit_world(world) {
+        Some(sc) => {
+            let visibility = world.ambient_occlusion.unwrap_or_default().visibility(world, sc.over_point, sc.normalv);
+            (Color::new(visibility, visibility, visibility), Some(sc))
+        },
+        None => (Color::WHITE, None),
     }
 }
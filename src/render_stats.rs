@@ -0,0 +1,131 @@
+use types::*;
+use crate::Canvas;
+use crate::RenderConfig;
+use crate::CancelToken;
+use crate::render_pixels;
+
+use std::fmt;
+
+// The ray tracing counters of a render, for the whole image and for each pixel, index is
+// y * width + x
+#[derive(Debug, Clone)]
+pub struct RenderStats {
+    pub width: usize,
+    pub height: usize,
+    pub total: RayStats,
+    // Rays traced through the world, camera, shadow, reflection and refraction rays
+    pub rays: Vec<usize>,
+    pub shape_tests: Vec<usize>,
+}
+
+impl RenderStats {
+    pub fn new(width: usize, height: usize) -> RenderStats {
+        RenderStats {
+            width,
+            height,
+            total: Default::default(),
+            rays: vec![0; width * height],
+            shape_tests: vec![0; width * height],
+        }
+    }
+
+    pub fn add_pixel(&mut self, x: usize, y: usize, stats: &RayStats) {
+        let i = y * self.width + x;
+        self.rays[i] += stats.world_intersections;
+        self.shape_tests[i] += stats.total_shape_tests();
+        self.total.add(stats);
+    }
+
+    // Black for pixels that took no work up to white for the most expensive pixel
+    pub fn rays_heatmap(&self) -> Canvas {
+        heatmap(self.width, self.height, &self.rays)
+    }
+
+    pub fn shape_tests_heatmap(&self) -> Canvas {
+        heatmap(self.width, self.height, &self.shape_tests)
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pixels = (self.width * self.height).max(1);
+        writeln!(f, "{}x{} pixels", self.width, self.height)?;
+        write!(f, "{}", self.total)?;
+        writeln!(f, "rays per pixel: {:.1} average, {} most",
+                 self.total.world_intersections as f64 / pixels as f64, self.rays.iter().max().unwrap_or(&0))?;
+        writeln!(f, "shape tests per pixel: {:.1} average, {} most",
+                 self.total.total_shape_tests() as f64 / pixels as f64, self.shape_tests.iter().max().unwrap_or(&0))
+    }
+}
+
+// Black through red and yellow to white as value goes from 0.0 to 1.0
+pub fn heat_color(value: f64) -> Color {
+    let v = value.clamp(0.0, 1.0) * 3.0;
+    Color::new(v.min(1.0), (v - 1.0).clamp(0.0, 1.0), (v - 2.0).clamp(0.0, 1.0))
+}
+
+fn heatmap(width: usize, height: usize, counts: &[usize]) -> Canvas {
+    let max = counts.iter().cloned().max().unwrap_or(0).max(1) as f64;
+    let mut canvas = Canvas::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            canvas.set_pixel(x, y, &heat_color(counts[y * width + x] as f64 / max));
+        }
+    }
+    canvas
+}
+
+// Turns counting on for a render, so the work for each pixel can be taken as it's done. If the
+// thread was already counting it carries on afterwards, with the render's counts added.
+pub(crate) struct PixelCounter {
+    earlier: Option<RayStats>,
+    counted: RayStats,
+}
+
+impl PixelCounter {
+    pub(crate) fn start() -> PixelCounter {
+        let earlier = if stats_enabled() { Some(take_stats()) } else { None };
+        enable_stats();
+        PixelCounter {
+            earlier,
+            counted: Default::default(),
+        }
+    }
+
+    // Adds everything counted since the last pixel to pixel x, y of stats
+    pub(crate) fn pixel_done(&mut self, stats: &mut RenderStats, x: usize, y: usize) {
+        let pixel = take_stats();
+        stats.add_pixel(x, y, &pixel);
+        self.counted.add(&pixel);
+    }
+}
+
+impl Drop for PixelCounter {
+    fn drop(&mut self) {
+        let rest = disable_stats();
+        if let Some(ref earlier) = self.earlier {
+            enable_stats();
+            let counted = &self.counted;
+            record_stats(|stats| {
+                stats.add(earlier);
+                stats.add(counted);
+                stats.add(&rest);
+            });
+        }
+    }
+}
+
+// Renders like render_with_config and counts the work done for every pixel, whether or not
+// config.stats is set. The summary is only printed when it is.
+pub fn render_with_stats(camera: &Camera, world: &World, canvas: &mut Canvas, config: &RenderConfig) -> RenderStats {
+    let counting = RenderConfig {
+        stats: true,
+        ..config.clone()
+    };
+    let (_, stats) = render_pixels(camera, world, canvas, &counting, &CancelToken::new(), |_| (), |_, _, _| ());
+    let stats = stats.unwrap_or_else(|| RenderStats::new(camera.hsize_px, camera.vsize_px));
+    if config.stats {
+        print!("{}", stats);
+    }
+    stats
+}
@@ -0,0 +1,53 @@
+use crate::Vector4D;
+use crate::Ray;
+use crate::World;
+use crate::Sampler;
+use crate::positive_hits;
+use crate::sample_cosine_hemisphere;
+
+// Darkens the ambient light in corners and where objects touch, by checking how much of the
+// hemisphere above a point is blocked by nearby objects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmbientOcclusion {
+    pub samples: usize,
+    // Objects further away than this don't block anything
+    pub max_distance: f64,
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: usize, max_distance: f64) -> AmbientOcclusion {
+        AmbientOcclusion {
+            samples,
+            max_distance,
+        }
+    }
+
+    // 1.0 when nothing is in the way of the hemisphere around normal, down to 0.0 when it's
+    // completely covered. point should be just above the surface, like over_point.
+    pub fn visibility(&self, world: &World, point: Vector4D, normal: Vector4D) -> f64 {
+        if self.samples == 0 {
+            return 1.0;
+        }
+        let mut sampler = Sampler::from_vectors(point, normal);
+        let mut open = 0;
+        for _ in 0..self.samples {
+            // cosine weighted, rays near the surface count less like they do for diffuse light
+            let dir = sample_cosine_hemisphere(normal, sampler.next_f64(), sampler.next_f64());
+            let xs = positive_hits(&Ray::new(point, dir).intersect_world(world));
+            let blocked = xs.iter().any(|i| i.t < self.max_distance && !i.obj.get_material().no_cast_shadow);
+            if !blocked {
+                open += 1;
+            }
+        }
+        open as f64 / self.samples as f64
+    }
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        AmbientOcclusion {
+            samples: 16,
+            max_distance: 1.0,
+        }
+    }
+}
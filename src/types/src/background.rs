@@ -0,0 +1,49 @@
+use crate::Color;
+use crate::Vector4D;
+use crate::BoxedUvPattern;
+use crate::cube_map;
+use std::f64::consts::PI;
+
+// What a ray sees when it doesn't hit anything, looked up by the direction of the ray.
+#[derive(Debug, Clone)]
+pub enum Background {
+    Color(Color),
+    // Blends from bottom looking straight down to top looking straight up
+    Gradient(Color, Color),
+    // Six images for the faces of a cube around the scene, in the same order as CubeMapPattern:
+    // left, front, right, back, up, down. The faces are seen from the inside of the cube.
+    CubeMap([BoxedUvPattern; 6]),
+    // A single image that wraps all the way around the scene, looking down -z is the center of
+    // the image.
+    Equirectangular(BoxedUvPattern),
+}
+
+impl Background {
+    pub fn color_at(&self, direction: Vector4D) -> Color {
+        let d = direction.normalized();
+        match *self {
+            Background::Color(color) => color,
+            Background::Gradient(bottom, top) => {
+                let fraction = (d.y + 1.0) / 2.0;
+                bottom + (top - bottom) * fraction
+            },
+            Background::CubeMap(ref faces) => {
+                // Push the direction out onto the unit cube
+                let max_c = d.x.abs().max(d.y.abs()).max(d.z.abs());
+                let (face, u, v) = cube_map(Vector4D::new_point(d.x / max_c, d.y / max_c, d.z / max_c));
+                faces[face as usize].uv_pattern_at(1.0 - u, v)
+            },
+            Background::Equirectangular(ref image) => {
+                let u = 0.5 + (-d.x).atan2(-d.z) / (2.0 * PI);
+                let v = 0.5 + d.y.max(-1.0).min(1.0).asin() / PI;
+                image.uv_pattern_at(u, v)
+            },
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Color(Color::BLACK)
+    }
+}
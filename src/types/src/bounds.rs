@@ -1,8 +1,69 @@
 use crate::Vector4D;
+use crate::Matrix4x4;
+use utils::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Bounds {
     pub min_point: Vector4D,
     pub max_point: Vector4D,
 }
 
+impl Bounds {
+    // Bounds around nothing, the union with any other bounds is the other bounds
+    pub fn empty() -> Bounds {
+        Bounds {
+            min_point: Vector4D::new_point(INFINITY, INFINITY, INFINITY),
+            max_point: Vector4D::new_point(-INFINITY, -INFINITY, -INFINITY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min_point.x > self.max_point.x || self.min_point.y > self.max_point.y || self.min_point.z > self.max_point.z
+    }
+
+    pub fn is_infinite(&self) -> bool {
+        let (lo, hi) = (self.min_point, self.max_point);
+        [lo.x, lo.y, lo.z, hi.x, hi.y, hi.z].iter().any(|c| c.abs() >= INFINITY)
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        let (a, b) = (self.min_point, other.min_point);
+        let (c, d) = (self.max_point, other.max_point);
+        Bounds {
+            min_point: Vector4D::new_point(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max_point: Vector4D::new_point(c.x.max(d.x), c.y.max(d.y), c.z.max(d.z)),
+        }
+    }
+
+    // The corners of the box, empty bounds have none
+    pub fn corners(&self) -> Vec<Vector4D> {
+        if self.is_empty() {
+            return vec![];
+        }
+        let (lo, hi) = (self.min_point, self.max_point);
+        (0..8).map(|i| {
+            let x = if i & 1 == 0 { lo.x } else { hi.x };
+            let y = if i & 2 == 0 { lo.y } else { hi.y };
+            let z = if i & 4 == 0 { lo.z } else { hi.z };
+            Vector4D::new_point(x, y, z)
+        }).collect()
+    }
+
+    // Axis aligned bounds around the box moved by m. Infinite bounds stay infinite on every
+    // axis, a rotation can turn an infinite extent along one axis into any other.
+    pub fn transform(&self, m: &Matrix4x4) -> Bounds {
+        if self.is_empty() {
+            return *self;
+        }
+        if self.is_infinite() {
+            return Bounds {
+                min_point: Vector4D::new_point(-INFINITY, -INFINITY, -INFINITY),
+                max_point: Vector4D::new_point(INFINITY, INFINITY, INFINITY),
+            };
+        }
+        self.corners().iter().fold(Bounds::empty(), |bounds, corner| {
+            let p = m.mul_vector4d(corner);
+            bounds.union(&Bounds { min_point: p, max_point: p })
+        })
+    }
+}
@@ -0,0 +1,113 @@
+use crate::Vector4D;
+use crate::Intersectable;
+use crate::BoxedUvPattern;
+use crate::UvMapping;
+use crate::fractal_noise;
+use std::fmt::Debug;
+
+// A bump perturbs the normal of a surface so it looks rough or detailed without changing the
+// geometry. It works in object space like patterns do, the perturbed normal is then taken back
+// to world space by the shape.
+pub trait Bumpable: BumpClone + Debug {
+    // object_normal is the normalized normal of the shape at object_point
+    fn perturb_normal(&self, object_point: Vector4D, object_normal: Vector4D) -> Vector4D;
+
+    fn normal_at_object(&self, obj: &dyn Intersectable, world_point: Vector4D) -> Vector4D {
+        let object_point = obj.world_to_object(world_point);
+        let mut object_normal = obj.normal_at_local(object_point);
+        object_normal.w = 0.0;
+        object_normal.normalize();
+        let mut perturbed = self.perturb_normal(object_point, object_normal);
+        perturbed.w = 0.0;
+        obj.normal_to_world(perturbed)
+    }
+}
+
+pub type BoxedBump = Box<dyn Bumpable + Send + Sync>;
+
+pub trait BumpClone {
+    fn clone_bump(&self) -> BoxedBump;
+}
+
+impl<T> BumpClone for T where T: 'static + Bumpable + Clone + Send + Sync {
+    fn clone_bump(&self) -> BoxedBump {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for BoxedBump {
+    fn clone(&self) -> Self {
+        self.clone_bump()
+    }
+}
+
+// Procedural bumps from the gradient of fractal noise. scale is the frequency of the bumps and
+// amplitude is how far the normal gets tilted.
+#[derive(Debug, Clone, Copy)]
+pub struct NoiseBump {
+    pub scale: f64,
+    pub amplitude: f64,
+    pub octaves: usize,
+    pub persistence: f64,
+}
+
+impl NoiseBump {
+    pub fn new(scale: f64, amplitude: f64) -> NoiseBump {
+        NoiseBump {
+            scale,
+            amplitude,
+            octaves: 3,
+            persistence: 0.5,
+        }
+    }
+
+    fn noise(&self, p: Vector4D) -> f64 {
+        fractal_noise(self.scale * p, self.octaves, self.persistence)
+    }
+}
+
+impl Bumpable for NoiseBump {
+    fn perturb_normal(&self, object_point: Vector4D, object_normal: Vector4D) -> Vector4D {
+        // central differences of the noise
+        let h = 0.001;
+        let dx = Vector4D::new_vector(h, 0.0, 0.0);
+        let dy = Vector4D::new_vector(0.0, h, 0.0);
+        let dz = Vector4D::new_vector(0.0, 0.0, h);
+        let gradient = Vector4D::new_vector(
+            self.noise(object_point + dx) - self.noise(object_point - dx),
+            self.noise(object_point + dy) - self.noise(object_point - dy),
+            self.noise(object_point + dz) - self.noise(object_point - dz)) / (2.0 * h * self.scale);
+        // only the part of the gradient along the surface tilts the normal
+        let tangential = gradient - gradient.dot(object_normal) * object_normal;
+        (object_normal - self.amplitude * tangential).normalized()
+    }
+}
+
+// A tangent space normal map, red/green/blue of the image are the tangent (increasing u),
+// bitangent (increasing v) and normal components of the new normal, scaled from 0..1 to -1..1.
+#[derive(Debug, Clone)]
+pub struct NormalMap {
+    pub image: BoxedUvPattern,
+    pub mapping: UvMapping,
+}
+
+impl NormalMap {
+    pub fn new(image: BoxedUvPattern, mapping: UvMapping) -> NormalMap {
+        NormalMap {
+            image,
+            mapping,
+        }
+    }
+}
+
+impl Bumpable for NormalMap {
+    fn perturb_normal(&self, object_point: Vector4D, object_normal: Vector4D) -> Vector4D {
+        let (u, v) = self.mapping.map(object_point);
+        let texel = self.image.uv_pattern_at(u, v);
+        let (tangent, bitangent) = self.mapping.tangent_frame(object_point);
+        let perturbed = (2.0 * texel.red - 1.0) * tangent +
+            (2.0 * texel.green - 1.0) * bitangent +
+            (2.0 * texel.blue - 1.0) * object_normal;
+        perturbed.normalized()
+    }
+}
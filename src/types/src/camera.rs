@@ -39,12 +39,17 @@ impl Camera {
 }
 
 pub fn ray_for_pixel(camera: &Camera, px: usize, py: usize) -> Ray {
-    // Offset in the canvas in world_space
     // We are adding .5 to go to pixels center, when I left this off, the test was off by
     // .004999
-    let xoffset = (px as f64 + 0.5) * camera.pixel_size;
-    let yoffset = (py as f64 + 0.5) * camera.pixel_size;
+    ray_for_canvas_point(camera, px as f64 + 0.5, py as f64 + 0.5)
+}
 
+// Ray through any point on the canvas, x and y are in pixels. Used to send several rays through
+// different parts of a pixel.
+pub fn ray_for_canvas_point(camera: &Camera, x: f64, y: f64) -> Ray {
+    // Offset in the canvas in world_space
+    let xoffset = x * camera.pixel_size;
+    let yoffset = y * camera.pixel_size;
     // x,y coordinate of the pixel in world space, (camera looks down at -z, so +x is to the left)
     let world_x = camera.half_width - xoffset;
     let world_y = camera.half_height - yoffset;
@@ -57,10 +62,6 @@ pub fn ray_for_pixel(camera: &Camera, px: usize, py: usize) -> Ray {
     // This is where the 4D part comes in I guess.
     let origin = camera.transform.inverse().mul_vector4d(&Vector4D::new_point(0.0, 0.0, 0.0)); 
     let dir = (world_pixel - origin).normalized();
-    let ray = Ray::new(origin, dir);
-    // DEBUG
-    if px == 3 && py == 70 {
-        println!("Ray for 3,70 bad px = {:?}", ray);
-    }
-    ray
+    Ray::new(origin, dir)
 }
+
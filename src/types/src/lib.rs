@@ -28,5 +28,41 @@ pub use camera::*;
 mod patterns;
 pub use patterns::*;
 
+mod noise;
+pub use noise::*;
+
+mod uv_mapping;
+pub use uv_mapping::*;
+
+mod background;
+pub use background::*;
+
+mod bump;
+pub use bump::*;
+
+mod microfacet;
+pub use microfacet::*;
+
+mod sampling;
+pub use sampling::*;
+
+mod path_tracer;
+pub use path_tracer::*;
+
+mod photon_map;
+pub use photon_map::*;
+
+mod media;
+pub use media::*;
+
+mod ambient_occlusion;
+pub use ambient_occlusion::*;
+
+mod stats;
+pub use stats::*;
+
+mod ray_record;
+pub use ray_record::*;
+
 mod bounds;
 pub use bounds::*;
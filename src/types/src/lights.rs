@@ -2,10 +2,8 @@ use crate::Color;
 use crate::Vector4D;
 use crate::reflect;
 use crate::Material;
-use crate::Intersectable;
 use crate::Shape;
-use crate::Pattern;
-use crate::Patternable;
+use crate::ShadingModel;
 #[derive(Copy,Clone, Debug)]
 pub struct LightSource {
     pub intensity: Color,
@@ -29,55 +27,19 @@ pub fn lighting(material: Material,
 
     //println!("lighting obj: {:?}\nshadow:{}", shape, shadow); 
    
-    let object : &dyn Intersectable;
-    match *shape {
-        Shape::Plane(ref o) => {
-            object = o;
-        },
-        Shape::Sphere(ref o ) => {
-            object = o;
-        },
-        Shape::TestShape(ref o) => {
-            object = o;
-        },
-        Shape::Cube(ref o) => {
-            object = o;
-        },
-        Shape::Cylinder(ref o) => {
-            object = o;
-        },
-        Shape::Cone(ref o) => {
-            object = o;
-        },
-        // FIXME: Definately not right, can't light up a group directly
-        Shape::Group(ref o) => {
-            // HACK:
-            return Color::BLACK;
-        },
+    let object = shape.as_intersectable();
+    let effective_color = material.color_at_object(object, point);
+    let lightv = (light.position - point).normalized();
 
-    }
-    let effective_color = match material.pattern {
-        None => { material.color },
-        Some(pattern) =>  {  match *pattern {
-            Pattern::StripePattern(pattern) => {
-                pattern.pattern_at_object(object, point) 
-            },
-            Pattern::RingPattern(pattern) => {
-                pattern.pattern_at_object(object, point)
-            },
-            Pattern::GradientPattern(pattern) => {
-                pattern.pattern_at_object(object, point)
-            },
-            Pattern::CheckeredPattern(pattern) => {
-                pattern.pattern_at_object(object, point)
-            },
-            Pattern::TestPattern(pattern) => {
-                pattern.pattern_at_object(object, point)
-            }
-        }
+    if let ShadingModel::Microfacet(ref microfacet) = material.shading {
+        let ambient = effective_color * light.intensity * material.ambient;
+        if is_shadow {
+            return ambient;
         }
-    } * light.intensity;
-    let lightv = (light.position - point).normalized();
+        return ambient + microfacet.shade(effective_color, light.intensity, lightv, eyev, normalv);
+    }
+
+    let effective_color = effective_color * light.intensity;
     let ambient = effective_color * material.ambient;
 
     let light_dot_normal = lightv.dot(normalv);
@@ -1,5 +1,12 @@
 use crate::Color;
-use crate::Pattern;
+use crate::Vector4D;
+use crate::Intersectable;
+use crate::Patternable;
+use crate::BoxedPattern;
+use crate::Bumpable;
+use crate::BoxedBump;
+use crate::ShadingModel;
+use crate::Microfacet;
 
 #[derive(Debug, Clone)]
 pub struct Material {
@@ -8,12 +15,34 @@ pub struct Material {
     pub diffuse: f64,
     pub specular: f64,
     pub shininess: f64,
-    pub pattern: Option<Box<Pattern>>,
+    // ambient still applies to the microfacet model, diffuse, specular and shininess are Phong only
+    pub shading: ShadingModel,
+    pub pattern: Option<BoxedPattern>,
+    // Perturbs the surface normal, for bump and normal mapping
+    pub bump: Option<BoxedBump>,
     pub no_cast_shadow: bool,
+    // Light given off by the surface, it's added to the shaded color whether or not the surface
+    // is lit. World::area_light_samples lets emissive shapes light up other objects.
+    pub emissive: Color,
     pub reflective: f64,
     pub refractive_index: f64,
+    // Wavelength dependent refractive index, refracted light is split into red, green and blue
+    // rays that bend by different amounts. refractive_index is still used for the Fresnel
+    // reflectance.
+    pub dispersion: Option<Dispersion>,
     pub transparency: f64,
-    
+    // Beer-Lambert absorption of light travelling through the inside of a transparent object.
+    // Each channel of absorption is how much of that color is absorbed, scaled by density,
+    // light is attenuated by exp(-absorption * density * distance).
+    pub absorption: Color,
+    pub absorption_density: f64,
+    // Spreads reflected and refracted rays over a cone for brushed metal and frosted glass,
+    // 0.0 is a perfect mirror / clear glass and 1.0 spreads them over the whole hemisphere.
+    pub reflect_roughness: f64,
+    pub refract_roughness: f64,
+    // How many rays are averaged for rough reflection and refraction. Every bounce between
+    // rough objects multiplies the number of rays.
+    pub glossy_samples: usize,
 }
 
 pub mod refractive_indices {
@@ -24,6 +53,44 @@ pub mod refractive_indices {
     pub const DEFAULT: f64 = VACUUM;
 }
 
+// Refractive index as a function of wavelength in micrometers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dispersion {
+    // n = a + b / wavelength^2
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum of b_i * wavelength^2 / (wavelength^2 - c_i)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub fn index_at(&self, wavelength: f64) -> f64 {
+        let l2 = wavelength * wavelength;
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            },
+        }
+    }
+}
+
+pub mod dispersions {
+    use crate::Dispersion;
+
+    // Wavelengths in micrometers the red, green and blue rays are traced at
+    pub const RED_WAVELENGTH: f64 = 0.65;
+    pub const GREEN_WAVELENGTH: f64 = 0.55;
+    pub const BLUE_WAVELENGTH: f64 = 0.45;
+
+    // Schott BK7 crown glass
+    pub const BK7_GLASS: Dispersion = Dispersion::Sellmeier {
+        b: [1.03961212, 0.231792344, 1.01046945],
+        c: [0.00600069867, 0.0200179144, 103.560653],
+    };
+    pub const DIAMOND: Dispersion = Dispersion::Cauchy { a: 2.385, b: 0.0117 };
+}
+
 impl Material {
     pub fn new(color: Color) -> Material {
         Material {
@@ -32,6 +99,65 @@ impl Material {
         }
     }
 
+    pub fn new_microfacet(color: Color, metallic: f64, roughness: f64) -> Material {
+        Material {
+            color,
+            shading: ShadingModel::Microfacet(Microfacet::new(metallic, roughness)),
+            ..Default::default()
+        }
+    }
+
+    // The color of the surface at point, from the pattern if there is one
+    pub fn color_at_object(&self, object: &dyn Intersectable, point: Vector4D) -> Color {
+        match self.pattern {
+            None => self.color,
+            Some(ref pattern) => pattern.pattern_at_object(object, point),
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emissive.red > 0.0 || self.emissive.green > 0.0 || self.emissive.blue > 0.0
+    }
+
+    // Also sets refractive_index to the index for green light
+    pub fn set_dispersion(&mut self, dispersion: Dispersion) {
+        self.refractive_index = dispersion.index_at(dispersions::GREEN_WAVELENGTH);
+        self.dispersion = Some(dispersion);
+    }
+
+    pub fn refractive_index_at(&self, wavelength: f64) -> f64 {
+        match self.dispersion {
+            Some(ref dispersion) => dispersion.index_at(wavelength),
+            None => self.refractive_index,
+        }
+    }
+
+    // How much light gets through distance units of this material
+    pub fn transmittance(&self, distance: f64) -> Color {
+        let channel = |absorption: f64| {
+            let coefficient = absorption * self.absorption_density;
+            if coefficient <= 0.0 {
+                1.0
+            } else {
+                (-coefficient * distance).exp()
+            }
+        };
+        Color::new(channel(self.absorption.red), channel(self.absorption.green), channel(self.absorption.blue))
+    }
+
+    pub fn is_absorbing(&self) -> bool {
+        self.absorption_density > 0.0 &&
+            (self.absorption.red > 0.0 || self.absorption.green > 0.0 || self.absorption.blue > 0.0)
+    }
+
+    pub fn set_pattern<P: 'static + Patternable + Clone + Send + Sync>(&mut self, pattern: P) {
+        self.pattern = Some(Box::new(pattern));
+    }
+
+    pub fn set_bump<B: 'static + Bumpable + Clone + Send + Sync>(&mut self, bump: B) {
+        self.bump = Some(Box::new(bump));
+    }
+
 }
 
 impl Default for Material {
@@ -42,11 +168,20 @@ impl Default for Material {
             diffuse: 0.9,
             specular: 0.9,
             shininess: 200.0,
+            shading: Default::default(),
             pattern:  None,
+            bump: None,
             no_cast_shadow: false,
+            emissive: Color::BLACK,
             reflective: 0.0,
             refractive_index: refractive_indices::DEFAULT,
+            dispersion: None,
             transparency: 0.0,
+            absorption: Color::BLACK,
+            absorption_density: 0.0,
+            reflect_roughness: 0.0,
+            refract_roughness: 0.0,
+            glossy_samples: 16,
         }
     }
 }
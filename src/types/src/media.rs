@@ -0,0 +1,157 @@
+use crate::Color;
+use crate::Ray;
+use crate::Shape;
+use crate::World;
+use std::f64::consts::PI;
+
+// A homogeneous participating medium like fog or smoke. Light travelling through it is
+// absorbed and scattered away (together the extinction), and light from the light source is
+// scattered into the ray, which gives glowing fog and light shafts.
+#[derive(Debug, Clone, Copy)]
+pub struct Medium {
+    // How much of each color is lost per unit of distance
+    pub absorption: Color,
+    pub scattering: Color,
+    // Henyey-Greenstein g, 0.0 scatters the same in all directions, towards 1.0 light mostly keeps
+    // going forward and towards -1.0 it mostly goes back.
+    pub anisotropy: f64,
+    // Samples along each stretch of the ray inside the medium for the in-scattered light
+    pub steps: usize,
+    // How far rays that don't hit anything go through the medium
+    pub max_distance: f64,
+}
+
+impl Medium {
+    pub fn new(absorption: f64, scattering: f64, anisotropy: f64) -> Medium {
+        Medium {
+            absorption: Color::new(absorption, absorption, absorption),
+            scattering: Color::new(scattering, scattering, scattering),
+            anisotropy,
+            steps: 16,
+            max_distance: 100.0,
+        }
+    }
+
+    pub fn extinction(&self) -> Color {
+        self.absorption + self.scattering
+    }
+}
+
+// A medium filling the inside of a shape. The shape isn't drawn, it's only used to find where
+// rays go in and out of the medium.
+#[derive(Debug, Clone)]
+pub struct Volume {
+    pub shape: Shape,
+    pub medium: Medium,
+}
+
+impl Volume {
+    pub fn new(shape: Shape, medium: Medium) -> Volume {
+        Volume {
+            shape,
+            medium,
+        }
+    }
+}
+
+// Henyey-Greenstein phase function, how much of the light is scattered through an angle with
+// the given cosine. Integrates to 1 over the sphere.
+pub fn henyey_greenstein(cos_theta: f64, g: f64) -> f64 {
+    let g2 = g * g;
+    let denom = 1.0 + g2 - 2.0 * g * cos_theta;
+    (1.0 - g2) / (4.0 * PI * denom * denom.sqrt())
+}
+
+fn exp_color(c: Color) -> Color {
+    Color::new((-c.red).exp(), (-c.green).exp(), (-c.blue).exp())
+}
+
+// The stretches of distance along a unit length ray that are inside each medium, up to t_end
+fn media_segments<'a>(world: &'a World, ray: &Ray, t_end: Option<f64>) -> Vec<(f64, f64, &'a Medium)> {
+    let mut segments = vec![];
+    if let Some(ref fog) = world.fog {
+        segments.push((0.0, t_end.unwrap_or(fog.max_distance), fog));
+    }
+    for volume in world.volumes.iter() {
+        let end = t_end.unwrap_or(volume.medium.max_distance);
+        let mut ts: Vec<f64> = volume.shape.intersect(ray).iter().map(|i| i.t).collect();
+        ts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for pair in ts.chunks(2) {
+            if pair.len() == 2 {
+                let (t0, t1) = (pair[0].max(0.0), pair[1].min(end));
+                if t1 > t0 {
+                    segments.push((t0, t1, &volume.medium));
+                }
+            }
+        }
+    }
+    segments
+}
+
+// Fraction of the light that makes it along ray for distance through all the media
+pub fn media_transmittance(world: &World, ray: &Ray, distance: f64) -> Color {
+    let ray = Ray::new(ray.origin(), ray.dir().normalized());
+    let optical_depth = media_segments(world, &ray, Some(distance)).iter()
+        .fold(Color::BLACK, |acc, (t0, t1, medium)| acc + medium.extinction() * (t1 - t0));
+    exp_color(optical_depth)
+}
+
+// The color seen along ray through the fog and volumes in world. color is what the ray sees at
+// t_end, or the background when None.
+pub fn integrate_media(world: &World, ray: &Ray, t_end: Option<f64>, color: Color) -> Color {
+    if world.fog.is_none() && world.volumes.is_empty() {
+        return color;
+    }
+    // work in distances along the ray
+    let speed = ray.dir().norm();
+    let ray = Ray::new(ray.origin(), ray.dir() / speed);
+    let t_end = t_end.map(|t| t * speed);
+    let segments = media_segments(world, &ray, t_end);
+    if segments.is_empty() {
+        return color;
+    }
+
+    // Between consecutive start and end points the same media are active
+    let mut breaks: Vec<f64> = segments.iter().flat_map(|(t0, t1, _)| vec![*t0, *t1]).collect();
+    breaks.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    breaks.dedup();
+
+    let light = world.light_source;
+    let mut transmittance = Color::WHITE;
+    let mut in_scattered = Color::BLACK;
+    for span in breaks.windows(2) {
+        let (start, end) = (span[0], span[1]);
+        let active: Vec<&Medium> = segments.iter()
+            .filter(|(t0, t1, _)| *t0 <= start && end <= *t1)
+            .map(|(_, _, medium)| *medium)
+            .collect();
+        if active.is_empty() {
+            continue;
+        }
+        let extinction = active.iter().fold(Color::BLACK, |acc, m| acc + m.extinction());
+        let steps = active.iter().map(|m| m.steps).max().unwrap_or(1).max(1);
+        let dt = (end - start) / steps as f64;
+        let step_transmittance = exp_color(extinction * dt);
+        for i in 0..steps {
+            let point = ray.at_t(start + (i as f64 + 0.5) * dt);
+            let mut to_light = light.position - point;
+            let light_distance = to_light.norm();
+            to_light.normalize();
+            if !world.is_shadowed(point) {
+                // cos of the angle between the light's direction and the direction towards the eye
+                let cos_theta = to_light.dot(ray.dir());
+                let light_in = light.intensity * media_transmittance(world, &Ray::new(point, to_light), light_distance);
+                // scattered light over this step, from half a step in on average
+                let scattered = active.iter().fold(Color::BLACK, |acc, m| {
+                    acc + m.scattering * henyey_greenstein(cos_theta, m.anisotropy)
+                });
+                // Lights don't get dimmer with distance, like in lighting they light an area
+                // facing them with PI times their intensity
+                let half_step = exp_color(extinction * (dt / 2.0));
+                in_scattered = in_scattered + transmittance * half_step * scattered * light_in * (PI * dt);
+            }
+            transmittance = transmittance * step_transmittance;
+        }
+    }
+    color * transmittance + in_scattered
+}
@@ -0,0 +1,106 @@
+use crate::Color;
+use crate::Vector4D;
+use std::f64::consts::PI;
+
+// Which reflection model lighting uses for a material
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadingModel {
+    // The book's model with ambient, diffuse, specular and shininess
+    Phong,
+    Microfacet(Microfacet),
+}
+
+impl Default for ShadingModel {
+    fn default() -> Self {
+        ShadingModel::Phong
+    }
+}
+
+// Physically based Cook-Torrance model with a GGX distribution of microfacets and Smith
+// shadowing. The base color is the material color (or pattern), metallic blends from a
+// dielectric to a metal and roughness goes from a mirror at 0.0 to fully rough at 1.0.
+// reflectance is the Fresnel reflectance of the dielectric looking straight at the surface,
+// metals use the base color instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Microfacet {
+    pub metallic: f64,
+    pub roughness: f64,
+    pub reflectance: f64,
+}
+
+// Below this the distribution becomes a spike that point lights can't hit
+const MIN_ROUGHNESS: f64 = 0.03;
+
+impl Microfacet {
+    pub fn new(metallic: f64, roughness: f64) -> Microfacet {
+        Microfacet {
+            metallic,
+            roughness,
+            ..Default::default()
+        }
+    }
+
+    // Fresnel reflectance at normal incidence
+    pub fn f0(&self, base_color: Color) -> Color {
+        let dielectric = Color::new(self.reflectance, self.reflectance, self.reflectance);
+        dielectric * (1.0 - self.metallic) + base_color * self.metallic
+    }
+
+    // The light reflected towards the eye from a light of the given intensity. Like the Phong
+    // model a white diffuse surface facing the light reflects the full intensity of the light,
+    // so the BRDF is scaled by PI.
+    pub fn shade(&self, base_color: Color, intensity: Color,
+                 lightv: Vector4D, eyev: Vector4D, normalv: Vector4D) -> Color {
+        let n_dot_l = normalv.dot(lightv);
+        if n_dot_l <= 0.0 {
+            return Color::BLACK;
+        }
+        // The eye can be just past the silhouette with bump maps and smooth triangles
+        let n_dot_v = normalv.dot(eyev).max(1e-4);
+        let halfv = (lightv + eyev).normalized();
+        let n_dot_h = normalv.dot(halfv).max(0.0);
+        let v_dot_h = eyev.dot(halfv).max(0.0);
+
+        let roughness = self.roughness.max(MIN_ROUGHNESS).min(1.0);
+        let alpha = roughness * roughness;
+        let fresnel = fresnel_schlick(v_dot_h, self.f0(base_color));
+        let d = ggx_distribution(n_dot_h, alpha);
+        let g = smith_shadowing(n_dot_l, alpha) * smith_shadowing(n_dot_v, alpha);
+        let specular = fresnel * (d * g / (4.0 * n_dot_l * n_dot_v));
+
+        // Light that isn't reflected at the surface gets into a dielectric and is scattered,
+        // metals absorb it.
+        let diffuse = (Color::WHITE - fresnel) * base_color * ((1.0 - self.metallic) / PI);
+
+        (diffuse + specular) * intensity * (PI * n_dot_l)
+    }
+}
+
+impl Default for Microfacet {
+    fn default() -> Self {
+        Microfacet {
+            metallic: 0.0,
+            roughness: 0.5,
+            reflectance: 0.04,
+        }
+    }
+}
+
+// GGX / Trowbridge-Reitz normal distribution, alpha is the roughness squared
+pub fn ggx_distribution(n_dot_h: f64, alpha: f64) -> f64 {
+    let alpha2 = alpha * alpha;
+    let denom = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    alpha2 / (PI * denom * denom)
+}
+
+// Smith shadowing/masking for one direction, the total is the product for the light and eye
+pub fn smith_shadowing(n_dot_x: f64, alpha: f64) -> f64 {
+    let alpha2 = alpha * alpha;
+    2.0 * n_dot_x / (n_dot_x + (alpha2 + (1.0 - alpha2) * n_dot_x * n_dot_x).sqrt())
+}
+
+// Schlick's approximation of the Fresnel reflectance for each color channel
+pub fn fresnel_schlick(cos: f64, f0: Color) -> Color {
+    let factor = (1.0 - cos).max(0.0).powi(5);
+    f0 + (Color::WHITE - f0) * factor
+}
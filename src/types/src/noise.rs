@@ -0,0 +1,112 @@
+use crate::Vector4D;
+
+// Ken Perlin's improved noise, the permutation table is the one from the reference
+// implementation. Indexes are masked to 0..255 instead of repeating the table twice.
+const PERMUTATION: [usize; 256] = [
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225,
+    140, 36, 103, 30, 69, 142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148,
+    247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219, 203, 117, 35, 11, 32,
+    57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
+    74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122,
+    60, 211, 133, 230, 220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54,
+    65, 25, 63, 161, 1, 216, 80, 73, 209, 76, 132, 187, 208, 89, 18, 169,
+    200, 196, 135, 130, 116, 188, 159, 86, 164, 100, 109, 198, 173, 186, 3, 64,
+    52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118, 126, 255, 82, 85, 212,
+    207, 206, 59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170, 213,
+    119, 248, 152, 2, 44, 154, 163, 70, 221, 153, 101, 155, 167, 43, 172, 9,
+    129, 22, 39, 253, 19, 98, 108, 110, 79, 113, 224, 232, 178, 185, 112, 104,
+    218, 246, 97, 228, 251, 34, 242, 193, 238, 210, 144, 12, 191, 179, 162, 241,
+    81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31, 181, 199, 106, 157,
+    184, 84, 204, 176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93,
+    222, 114, 67, 29, 24, 72, 243, 141, 128, 195, 78, 66, 215, 61, 156, 180,
+];
+
+fn perm(i: usize) -> usize {
+    PERMUTATION[i & 255]
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+// Convert low 4 bits of the hash into one of 12 gradient directions and dot it with x,y,z
+fn grad(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+// 3D gradient noise, the result is roughly in the range -1.0 to 1.0 and is 0.0 at every integer
+// lattice point.
+pub fn perlin_noise(x: f64, y: f64, z: f64) -> f64 {
+    let xi = (x.floor() as i64 & 255) as usize;
+    let yi = (y.floor() as i64 & 255) as usize;
+    let zi = (z.floor() as i64 & 255) as usize;
+
+    // position inside of the unit cube
+    let x = x - x.floor();
+    let y = y - y.floor();
+    let z = z - z.floor();
+
+    let u = fade(x);
+    let v = fade(y);
+    let w = fade(z);
+
+    // hash the 8 corners of the cube
+    let a = perm(xi) + yi;
+    let aa = perm(a) + zi;
+    let ab = perm(a + 1) + zi;
+    let b = perm(xi + 1) + yi;
+    let ba = perm(b) + zi;
+    let bb = perm(b + 1) + zi;
+
+    lerp(w, lerp(v, lerp(u, grad(perm(aa), x, y, z),
+                            grad(perm(ba), x - 1.0, y, z)),
+                    lerp(u, grad(perm(ab), x, y - 1.0, z),
+                            grad(perm(bb), x - 1.0, y - 1.0, z))),
+            lerp(v, lerp(u, grad(perm(aa + 1), x, y, z - 1.0),
+                            grad(perm(ba + 1), x - 1.0, y, z - 1.0)),
+                    lerp(u, grad(perm(ab + 1), x, y - 1.0, z - 1.0),
+                            grad(perm(bb + 1), x - 1.0, y - 1.0, z - 1.0))))
+}
+
+pub fn perlin_noise_at(point: Vector4D) -> f64 {
+    perlin_noise(point.x, point.y, point.z)
+}
+
+// Sum octaves of noise, each octave doubles the frequency and the amplitude is multiplied by
+// persistence. The result is normalized back into the range of a single octave.
+pub fn fractal_noise(point: Vector4D, octaves: usize, persistence: f64) -> f64 {
+    let mut total = 0.0;
+    let mut frequency = 1.0;
+    let mut amplitude = 1.0;
+    let mut max_value = 0.0;
+    for _ in 0..octaves.max(1) {
+        total += perlin_noise(point.x * frequency, point.y * frequency, point.z * frequency) * amplitude;
+        max_value += amplitude;
+        amplitude *= persistence;
+        frequency *= 2.0;
+    }
+    total / max_value
+}
+
+// Same as fractal_noise but sums the absolute value of each octave, this gives the sharp creases
+// used by marble and wood. The result is in the range 0.0 to 1.0.
+pub fn turbulence(point: Vector4D, octaves: usize, persistence: f64) -> f64 {
+    let mut total = 0.0;
+    let mut frequency = 1.0;
+    let mut amplitude = 1.0;
+    let mut max_value = 0.0;
+    for _ in 0..octaves.max(1) {
+        total += perlin_noise(point.x * frequency, point.y * frequency, point.z * frequency).abs() * amplitude;
+        max_value += amplitude;
+        amplitude *= persistence;
+        frequency *= 2.0;
+    }
+    total / max_value
+}
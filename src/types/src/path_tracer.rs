@@ -0,0 +1,161 @@
+use crate::Color;
+use crate::Vector4D;
+use crate::Ray;
+use crate::Shape;
+use crate::ShadingModel;
+use crate::World;
+use crate::ShadeComputation;
+use crate::Sampler;
+use crate::lighting;
+use crate::schlick;
+use crate::refraction_direction;
+use crate::sample_cone;
+use crate::sample_cosine_hemisphere;
+use crate::dispersions;
+use crate::refractive_indices;
+use std::f64::consts::PI;
+
+// Bounces before russian roulette starts ending paths that don't carry much light
+const ROULETTE_DEPTH: usize = 3;
+
+// Color seen along ray with a Monte Carlo path tracer. Unlike color_at a single path is followed,
+// at each hit the light source is sampled directly and then one of diffuse, reflection or
+// refraction is picked for the next bounce, in proportion to how much the material does of each.
+// Averaging many paths gives indirect light and color bleeding that color_at doesn't have.
+// The ambient term of materials is left out since indirect light replaces it.
+pub fn path_trace(world: &World, ray: Ray, max_depth: usize, sampler: &mut Sampler) -> Color {
+    path_trace_and_hit(world, ray, max_depth, sampler).0
+}
+
+// Like path_trace, also returns the computations of the first hit of the path
+pub fn path_trace_and_hit(world: &World, ray: Ray, max_depth: usize, sampler: &mut Sampler) -> (Color, Option<ShadeComputation>) {
+    let mut ray = ray;
+    let mut first_hit = None;
+    let mut radiance = Color::BLACK;
+    let mut throughput = Color::WHITE;
+    // Emissive surfaces found by a diffuse bounce are already counted by World::area_light
+    let mut counts_emission = true;
+    // The object the ray is travelling through, for absorption
+    let mut medium: Option<Box<Shape>> = None;
+
+    for depth in 0..=max_depth {
+        let sc = match ray.hit_world(world) {
+            Some(sc) => sc,
+            None => {
+                radiance = radiance + throughput * world.background.color_at(ray.dir());
+                break;
+            },
+        };
+        if let Some(ref medium) = medium {
+            throughput = throughput * medium.get_material().transmittance(sc.t * ray.dir().norm());
+        }
+        if depth == 0 {
+            first_hit = Some(sc.clone());
+        }
+        let material = sc.obj.get_material();
+
+        if counts_emission || world.area_light_samples == 0 {
+            radiance = radiance + throughput * material.emissive;
+        }
+
+        // Next event estimation, light from the light source and emissive objects
+        let mut no_ambient = material.clone();
+        no_ambient.ambient = 0.0;
+        let direct = lighting(no_ambient, &sc.obj, world.light_source,
+                              sc.over_point, sc.eyev, sc.normalv, world.is_shadowed(sc.over_point));
+        radiance = radiance + throughput * (direct + world.area_light(&sc));
+
+        if depth == max_depth {
+            break;
+        }
+
+        // How much of each kind of bounce the material does, matching shade_hit
+        let (reflect_weight, refract_weight) = if material.reflective > 0.0 && material.transparency > 0.0 {
+            let reflectance = schlick(&sc);
+            (reflectance, (1.0 - reflectance) * material.transparency)
+        } else {
+            (material.reflective, material.transparency)
+        };
+        let metallic = match material.shading {
+            ShadingModel::Phong => 0.0,
+            ShadingModel::Microfacet(ref microfacet) => microfacet.metallic,
+        };
+        let albedo = material.color_at_object(sc.obj.as_intersectable(), sc.over_point) *
+            (material.diffuse * (1.0 - metallic));
+        let diffuse_weight = (albedo.red + albedo.green + albedo.blue) / 3.0;
+        let total_weight = reflect_weight + refract_weight + diffuse_weight;
+        if total_weight <= 0.0 {
+            break;
+        }
+
+        // Pick one bounce, dividing by the chance of picking it keeps the average right
+        let choice = sampler.next_f64() * total_weight;
+        if choice < diffuse_weight {
+            let dir = sample_cosine_hemisphere(sc.normalv, sampler.next_f64(), sampler.next_f64());
+            // the cosine and 1/PI of the diffuse BRDF cancel with the pdf of the direction
+            throughput = throughput * albedo * (total_weight / diffuse_weight);
+            ray = Ray::new(sc.over_point, dir);
+            counts_emission = false;
+            medium = sc.n1_medium.clone();
+        } else if choice < diffuse_weight + reflect_weight {
+            let dir = rough_direction(sc.reflectv, sc.normalv, material.reflect_roughness, sampler);
+            throughput = throughput * total_weight;
+            ray = Ray::new(sc.over_point, dir);
+            counts_emission = true;
+            medium = sc.n1_medium.clone();
+        } else {
+            // Dispersive materials refract one randomly picked color per path
+            let dispersive = [&sc.n1_medium, &sc.n2_medium].iter()
+                .any(|medium| medium.as_ref().map_or(false, |m| m.get_material().dispersion.is_some()));
+            let (n1, n2, channel) = if dispersive {
+                let wavelengths = [dispersions::RED_WAVELENGTH, dispersions::GREEN_WAVELENGTH,
+                                   dispersions::BLUE_WAVELENGTH];
+                let channel = ((sampler.next_f64() * 3.0) as usize).min(2);
+                let index_at = |medium: &Option<Box<Shape>>| match medium {
+                    Some(m) => m.get_material().refractive_index_at(wavelengths[channel]),
+                    None => refractive_indices::VACUUM,
+                };
+                (index_at(&sc.n1_medium), index_at(&sc.n2_medium), Some(channel))
+            } else {
+                (sc.n1, sc.n2, None)
+            };
+            let dir = match refraction_direction(&sc, n1, n2) {
+                Some(dir) => dir,
+                // total internal reflection, shade_hit doesn't bring any light back either
+                None => break,
+            };
+            let dir = rough_direction(dir, -sc.normalv, material.refract_roughness, sampler);
+            throughput = throughput * total_weight;
+            if let Some(channel) = channel {
+                let mut mask = [0.0; 3];
+                mask[channel] = 3.0;
+                throughput = throughput * Color::new(mask[0], mask[1], mask[2]);
+            }
+            ray = Ray::new(sc.under_point, dir);
+            counts_emission = true;
+            medium = sc.n2_medium.clone();
+        }
+
+        if depth >= ROULETTE_DEPTH {
+            let survive = throughput.red.max(throughput.green).max(throughput.blue).min(0.95);
+            if sampler.next_f64() >= survive {
+                break;
+            }
+            throughput = throughput * (1.0 / survive);
+        }
+    }
+    (radiance, first_hit)
+}
+
+// Spread direction in the cone set by roughness, kept on the side of the surface side points to
+fn rough_direction(direction: Vector4D, side: Vector4D, roughness: f64, sampler: &mut Sampler) -> Vector4D {
+    if roughness <= 0.0 {
+        return direction;
+    }
+    let mut dir = sample_cone(direction, roughness.min(1.0) * PI / 2.0, sampler.next_f64(), sampler.next_f64());
+    let d = dir.dot(side);
+    if d < 0.0 {
+        dir = dir - (2.0 * d) * side;
+    }
+    dir
+}
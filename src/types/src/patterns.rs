@@ -2,6 +2,8 @@ use crate::Color;
 use crate::Matrix4x4;
 use crate::Vector4D;
 use crate::Intersectable;
+use crate::fractal_noise;
+use crate::turbulence;
 use std::fmt::Debug;
 
 #[derive(Debug, Clone)]
@@ -13,35 +15,135 @@ pub enum Pattern {
     TestPattern(TestPattern),
 }
 
-pub trait Patternable {
+impl Pattern {
+    fn as_patternable(&self) -> &dyn Patternable {
+        match *self {
+            Pattern::StripePattern(ref p) => p,
+            Pattern::RingPattern(ref p) => p,
+            Pattern::GradientPattern(ref p) => p,
+            Pattern::CheckeredPattern(ref p) => p,
+            Pattern::TestPattern(ref p) => p,
+        }
+    }
+
+    fn as_patternable_mut(&mut self) -> &mut dyn Patternable {
+        match *self {
+            Pattern::StripePattern(ref mut p) => p,
+            Pattern::RingPattern(ref mut p) => p,
+            Pattern::GradientPattern(ref mut p) => p,
+            Pattern::CheckeredPattern(ref mut p) => p,
+            Pattern::TestPattern(ref mut p) => p,
+        }
+    }
+}
+
+// The built in patterns can still be wrapped in the Pattern enum, it just forwards to the wrapped
+// pattern.
+impl Patternable for Pattern {
+    fn set_transform(&mut self, transform: Matrix4x4) {
+        self.as_patternable_mut().set_transform(transform)
+    }
+
+    fn get_transform(&self) -> Matrix4x4 {
+        self.as_patternable().get_transform()
+    }
+
+    fn pattern_at(&self, point: Vector4D) -> Color {
+        self.as_patternable().pattern_at(point)
+    }
+}
+
+// Materials hold their pattern as a trait object so that crates outside of types can supply their
+// own patterns, anything that is Patternable + Clone + Send + Sync can be boxed up.
+pub type BoxedPattern = Box<dyn Patternable + Send + Sync>;
+
+pub trait PatternClone {
+    fn clone_pattern(&self) -> BoxedPattern;
+}
+
+impl<T> PatternClone for T where T: 'static + Patternable + Clone + Send + Sync {
+    fn clone_pattern(&self) -> BoxedPattern {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for BoxedPattern {
+    fn clone(&self) -> Self {
+        self.clone_pattern()
+    }
+}
+
+pub trait Patternable: PatternClone + Debug {
     fn set_transform(&mut self, transform: Matrix4x4);
     fn get_transform(&self) -> Matrix4x4;
     fn pattern_at(&self, point: Vector4D) -> Color;
     fn pattern_at_object(&self, obj: &dyn Intersectable, world_point: Vector4D) -> Color {
         let object_point = obj.world_to_object(world_point);
-        let pattern_point = self.get_transform().inverse().mul_vector4d(&object_point);
+        self.pattern_at_nested(object_point)
+    }
+    // Evaluate the pattern at a point given in the space of whatever contains it, either the
+    // object or an enclosing pattern. The pattern's own transform is applied on top.
+    fn pattern_at_nested(&self, outer_point: Vector4D) -> Color {
+        let pattern_point = self.get_transform().inverse().mul_vector4d(&outer_point);
         self.pattern_at(pattern_point)
     }
 }
 
+// Each half of a two part pattern (stripes, rings, ...) is either a solid color or another
+// pattern, so we can have stripes of checkers etc.
+#[derive(Debug, Clone)]
+pub enum PatternComponent {
+    Color(Color),
+    Pattern(BoxedPattern),
+}
+
+impl PatternComponent {
+    // point is in the space of the pattern that holds this component
+    pub fn color_at(&self, point: Vector4D) -> Color {
+        match *self {
+            PatternComponent::Color(color) => color,
+            PatternComponent::Pattern(ref pattern) => pattern.pattern_at_nested(point),
+        }
+    }
+}
+
+impl From<Color> for PatternComponent {
+    fn from(color: Color) -> Self {
+        PatternComponent::Color(color)
+    }
+}
+
+impl<P> From<P> for PatternComponent where P: 'static + Patternable + Clone + Send + Sync {
+    fn from(pattern: P) -> Self {
+        PatternComponent::Pattern(Box::new(pattern))
+    }
+}
+
 // A strip pattern creates a pattern that alternatives along the x-axis
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct StripePattern {
-    pub colors: [Color; 2],
+    pub colors: [PatternComponent; 2],
     pub transform: Matrix4x4,
 }
 
 impl StripePattern {
     pub fn new(color_1: Color, color_2: Color) -> StripePattern {
         StripePattern {
-            colors: [color_1, color_2],
+            colors: [color_1.into(), color_2.into()],
+            transform: Matrix4x4::new(),
+        }
+    }
+
+    pub fn new_nested<A: Into<PatternComponent>, B: Into<PatternComponent>>(a: A, b: B) -> StripePattern {
+        StripePattern {
+            colors: [a.into(), b.into()],
             transform: Matrix4x4::new(),
         }
     }
 
     pub fn test() -> StripePattern {
         StripePattern {
-            colors: [Color::new(1.0, 1.5, 2.0), Color::new(0.75, 0.5, 0.25)],
+            colors: [Color::new(1.0, 1.5, 2.0).into(), Color::new(0.75, 0.5, 0.25).into()],
             transform: Matrix4x4::new(),
         }
     }
@@ -67,9 +169,9 @@ impl Patternable for StripePattern {
 
     fn pattern_at(&self, point: Vector4D) -> Color {
         if point.x.floor() as i64 % 2 == 0 {
-            self.colors[0]
+            self.colors[0].color_at(point)
         } else {
-            self.colors[1]
+            self.colors[1].color_at(point)
         }
     }
 
@@ -78,23 +180,30 @@ impl Patternable for StripePattern {
 impl Default for StripePattern {
     fn default() -> Self {
         StripePattern {
-            colors: [Color::white(), Color::BLACK],
+            colors: [Color::white().into(), Color::BLACK.into()],
             transform: Matrix4x4::new()
         }
     }
 }
 
 // A ring pattern pattern creates a pattern that alternatives colors in concentric rings 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct RingPattern {
-    pub colors: [Color; 2],
+    pub colors: [PatternComponent; 2],
     pub transform: Matrix4x4,
 }
 
 impl RingPattern {
     pub fn new(color_1: Color, color_2: Color) -> RingPattern {
         RingPattern {
-            colors: [color_1, color_2],
+            colors: [color_1.into(), color_2.into()],
+            transform: Matrix4x4::new(),
+        }
+    }
+
+    pub fn new_nested<A: Into<PatternComponent>, B: Into<PatternComponent>>(a: A, b: B) -> RingPattern {
+        RingPattern {
+            colors: [a.into(), b.into()],
             transform: Matrix4x4::new(),
         }
     }
@@ -119,9 +228,9 @@ impl Patternable for RingPattern {
 
     fn pattern_at(&self, point: Vector4D) -> Color {
         if (point.x.powi(2) + point.z.powi(2)).sqrt().floor() as i64 % 2 == 0 {
-            self.colors[0]
+            self.colors[0].color_at(point)
         } else {
-            self.colors[1]
+            self.colors[1].color_at(point)
         }
     }
 
@@ -130,7 +239,7 @@ impl Patternable for RingPattern {
 impl Default for RingPattern {
     fn default() -> Self {
         RingPattern {
-            colors: [Color::white(), Color::BLACK],
+            colors: [Color::white().into(), Color::BLACK.into()],
             transform: Matrix4x4::new()
         }
     }
@@ -139,16 +248,23 @@ impl Default for RingPattern {
 // A gradient pattern pattern creates a pattern that interpolates between 2 colores along the
 // x-axis 
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct GradientPattern {
-    pub colors: [Color; 2],
+    pub colors: [PatternComponent; 2],
     pub transform: Matrix4x4,
 }
 
 impl GradientPattern {
     pub fn new(color_1: Color, color_2: Color) -> GradientPattern {
         GradientPattern {
-            colors: [color_1, color_2],
+            colors: [color_1.into(), color_2.into()],
+            transform: Matrix4x4::new(),
+        }
+    }
+
+    pub fn new_nested<A: Into<PatternComponent>, B: Into<PatternComponent>>(a: A, b: B) -> GradientPattern {
+        GradientPattern {
+            colors: [a.into(), b.into()],
             transform: Matrix4x4::new(),
         }
     }
@@ -172,9 +288,11 @@ impl Patternable for GradientPattern {
     }
 
     fn pattern_at(&self, point: Vector4D) -> Color {
-        let distance = self.colors[1] - self.colors[0];
+        let color_1 = self.colors[0].color_at(point);
+        let color_2 = self.colors[1].color_at(point);
+        let distance = color_2 - color_1;
         let fraction = point.x - point.x.floor();
-        return self.colors[0] + distance * fraction;
+        return color_1 + distance * fraction;
     }
 
 }
@@ -182,7 +300,7 @@ impl Patternable for GradientPattern {
 impl Default for GradientPattern {
     fn default() -> Self {
         GradientPattern {
-            colors: [Color::white(), Color::BLACK],
+            colors: [Color::white().into(), Color::BLACK.into()],
             transform: Matrix4x4::new()
         }
     }
@@ -191,19 +309,45 @@ impl Default for GradientPattern {
 // A checkered pattern pattern creates a pattern that interpolates between 2 colores along the
 // x-axis 
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct CheckeredPattern {
-    pub colors: [Color; 2],
+    pub colors: [PatternComponent; 2],
+    // size of a check along the x, y and z axis
+    pub sizes: [f64; 3],
     pub transform: Matrix4x4,
 }
 
 impl CheckeredPattern {
     pub fn new(color_1: Color, color_2: Color) -> CheckeredPattern {
         CheckeredPattern {
-            colors: [color_1, color_2],
+            colors: [color_1.into(), color_2.into()],
+            sizes: [1.0, 1.0, 1.0],
             transform: Matrix4x4::new(),
         }
     }
+
+    pub fn new_nested<A: Into<PatternComponent>, B: Into<PatternComponent>>(a: A, b: B) -> CheckeredPattern {
+        CheckeredPattern {
+            colors: [a.into(), b.into()],
+            sizes: [1.0, 1.0, 1.0],
+            transform: Matrix4x4::new(),
+        }
+    }
+
+    // Sizes that aren't positive are clamped to a tiny positive size, a zero size would divide
+    // by zero in pattern_at
+    pub fn new_sized(color_1: Color, color_2: Color, size_x: f64, size_y: f64, size_z: f64) -> CheckeredPattern {
+        CheckeredPattern {
+            colors: [color_1.into(), color_2.into()],
+            sizes: [checker_size(size_x), checker_size(size_y), checker_size(size_z)],
+            transform: Matrix4x4::new(),
+        }
+    }
+}
+
+// NaN is clamped too, max returns the other value
+fn checker_size(size: f64) -> f64 {
+    size.max(utils::EPSILON)
 }
 
 
@@ -224,11 +368,13 @@ impl Patternable for CheckeredPattern {
     }
 
     fn pattern_at(&self, point: Vector4D) -> Color {
-        let sum = point.x.floor() + point.y.floor() + point.z.floor();
+        let sum = (point.x / checker_size(self.sizes[0])).floor() + 
+            (point.y / checker_size(self.sizes[1])).floor() + 
+            (point.z / checker_size(self.sizes[2])).floor();
         if sum as i64 % 2 == 0 {
-            self.colors[0]
+            self.colors[0].color_at(point)
         } else {
-            self.colors[1]
+            self.colors[1].color_at(point)
         }
     }
 
@@ -237,12 +383,308 @@ impl Patternable for CheckeredPattern {
 impl Default for CheckeredPattern {
     fn default() -> Self {
         CheckeredPattern {
-            colors: [Color::white(), Color::BLACK],
+            colors: [Color::white().into(), Color::BLACK.into()],
+            sizes: [1.0, 1.0, 1.0],
             transform: Matrix4x4::new()
         }
     }
 }
 
+// A radial gradient interpolates between 2 colors by the distance from the y-axis, or from the
+// origin when spherical is set. Like GradientPattern it repeats every unit.
+#[derive(Debug, Clone)]
+pub struct RadialGradientPattern {
+    pub colors: [PatternComponent; 2],
+    pub spherical: bool,
+    pub transform: Matrix4x4,
+}
+
+impl RadialGradientPattern {
+    pub fn new(color_1: Color, color_2: Color) -> RadialGradientPattern {
+        RadialGradientPattern {
+            colors: [color_1.into(), color_2.into()],
+            spherical: false,
+            transform: Matrix4x4::new(),
+        }
+    }
+
+    pub fn new_spherical(color_1: Color, color_2: Color) -> RadialGradientPattern {
+        RadialGradientPattern {
+            colors: [color_1.into(), color_2.into()],
+            spherical: true,
+            transform: Matrix4x4::new(),
+        }
+    }
+}
+
+impl Patternable for RadialGradientPattern {
+    fn get_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4x4) {
+        self.transform = transform;
+    }
+
+    fn pattern_at(&self, point: Vector4D) -> Color {
+        let distance = if self.spherical {
+            (point.x.powi(2) + point.y.powi(2) + point.z.powi(2)).sqrt()
+        } else {
+            (point.x.powi(2) + point.z.powi(2)).sqrt()
+        };
+        let fraction = distance - distance.floor();
+        let color_1 = self.colors[0].color_at(point);
+        let color_2 = self.colors[1].color_at(point);
+        color_1 + (color_2 - color_1) * fraction
+    }
+
+}
+
+// A ring gradient is a RingPattern without the hard edges, each ring blends from the first color
+// to the second and back again.
+#[derive(Debug, Clone)]
+pub struct RingGradientPattern {
+    pub colors: [PatternComponent; 2],
+    pub transform: Matrix4x4,
+}
+
+impl RingGradientPattern {
+    pub fn new(color_1: Color, color_2: Color) -> RingGradientPattern {
+        RingGradientPattern {
+            colors: [color_1.into(), color_2.into()],
+            transform: Matrix4x4::new(),
+        }
+    }
+}
+
+impl Patternable for RingGradientPattern {
+    fn get_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4x4) {
+        self.transform = transform;
+    }
+
+    fn pattern_at(&self, point: Vector4D) -> Color {
+        let distance = (point.x.powi(2) + point.z.powi(2)).sqrt();
+        let fraction = 1.0 - (2.0 * (distance - distance.floor()) - 1.0).abs();
+        let color_1 = self.colors[0].color_at(point);
+        let color_2 = self.colors[1].color_at(point);
+        color_1 + (color_2 - color_1) * fraction
+    }
+
+}
+
+// A solid pattern is a single color everywhere, handy when something wants a pattern
+#[derive(Debug, Clone, Copy)]
+pub struct SolidPattern {
+    pub color: Color,
+    pub transform: Matrix4x4,
+}
+
+impl SolidPattern {
+    pub fn new(color: Color) -> SolidPattern {
+        SolidPattern {
+            color,
+            transform: Matrix4x4::new(),
+        }
+    }
+}
+
+impl Patternable for SolidPattern {
+    fn get_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4x4) {
+        self.transform = transform;
+    }
+
+    fn pattern_at(&self, _point: Vector4D) -> Color {
+        self.color
+    }
+
+}
+
+// A blended pattern mixes two patterns together, weight is how much of the second pattern is
+// used, 0.5 averages the two.
+#[derive(Debug, Clone)]
+pub struct BlendedPattern {
+    pub patterns: [BoxedPattern; 2],
+    pub weight: f64,
+    pub transform: Matrix4x4,
+}
+
+impl BlendedPattern {
+    pub fn new<A, B>(pattern_1: A, pattern_2: B) -> BlendedPattern 
+        where A: 'static + Patternable + Clone + Send + Sync,
+              B: 'static + Patternable + Clone + Send + Sync {
+        BlendedPattern::new_weighted(pattern_1, pattern_2, 0.5)
+    }
+
+    pub fn new_weighted<A, B>(pattern_1: A, pattern_2: B, weight: f64) -> BlendedPattern 
+        where A: 'static + Patternable + Clone + Send + Sync,
+              B: 'static + Patternable + Clone + Send + Sync {
+        BlendedPattern {
+            patterns: [Box::new(pattern_1), Box::new(pattern_2)],
+            weight,
+            transform: Matrix4x4::new(),
+        }
+    }
+}
+
+impl Patternable for BlendedPattern {
+    fn get_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4x4) {
+        self.transform = transform;
+    }
+
+    fn pattern_at(&self, point: Vector4D) -> Color {
+        let color_1 = self.patterns[0].pattern_at_nested(point);
+        let color_2 = self.patterns[1].pattern_at_nested(point);
+        color_1 * (1.0 - self.weight) + color_2 * self.weight
+    }
+
+}
+
+// A perturbed pattern jitters the point with noise before looking up the inner pattern, scale is
+// how far the point can move.
+#[derive(Debug, Clone)]
+pub struct PerturbedPattern {
+    pub pattern: BoxedPattern,
+    pub scale: f64,
+    pub octaves: usize,
+    pub persistence: f64,
+    pub transform: Matrix4x4,
+}
+
+impl PerturbedPattern {
+    pub fn new<P: 'static + Patternable + Clone + Send + Sync>(pattern: P) -> PerturbedPattern {
+        PerturbedPattern {
+            pattern: Box::new(pattern),
+            scale: 0.2,
+            octaves: 1,
+            persistence: 0.5,
+            transform: Matrix4x4::new(),
+        }
+    }
+}
+
+impl Patternable for PerturbedPattern {
+    fn get_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4x4) {
+        self.transform = transform;
+    }
+
+    fn pattern_at(&self, point: Vector4D) -> Color {
+        // Sample the noise at offset points for y and z so each axis is jittered differently
+        let dx = fractal_noise(point, self.octaves, self.persistence);
+        let dy = fractal_noise(point + Vector4D::new_vector(31.4, 0.0, 0.0), self.octaves, self.persistence);
+        let dz = fractal_noise(point + Vector4D::new_vector(0.0, 0.0, 27.1), self.octaves, self.persistence);
+        let jittered = point + self.scale * Vector4D::new_vector(dx, dy, dz);
+        self.pattern.pattern_at_nested(jittered)
+    }
+
+}
+
+// A marble pattern is bands along the x-axis that are bent by turbulence. frequency is the
+// number of bands per unit and turbulence is how much the bands are distorted.
+#[derive(Debug, Clone)]
+pub struct MarblePattern {
+    pub colors: [PatternComponent; 2],
+    pub frequency: f64,
+    pub turbulence: f64,
+    pub octaves: usize,
+    pub persistence: f64,
+    pub transform: Matrix4x4,
+}
+
+impl MarblePattern {
+    pub fn new(color_1: Color, color_2: Color) -> MarblePattern {
+        MarblePattern {
+            colors: [color_1.into(), color_2.into()],
+            frequency: 1.0,
+            turbulence: 5.0,
+            octaves: 4,
+            persistence: 0.5,
+            transform: Matrix4x4::new(),
+        }
+    }
+}
+
+impl Patternable for MarblePattern {
+    fn get_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4x4) {
+        self.transform = transform;
+    }
+
+    fn pattern_at(&self, point: Vector4D) -> Color {
+        let noise = turbulence(point, self.octaves, self.persistence);
+        let bands = (point.x * self.frequency * std::f64::consts::PI + self.turbulence * noise).sin();
+        let fraction = (bands + 1.0) / 2.0;
+        let color_1 = self.colors[0].color_at(point);
+        let color_2 = self.colors[1].color_at(point);
+        color_1 + (color_2 - color_1) * fraction
+    }
+
+}
+
+// A wood pattern is concentric rings around the y-axis, like RingPattern, but the ring distance is
+// distorted with turbulence and the colors blend across each ring.
+#[derive(Debug, Clone)]
+pub struct WoodPattern {
+    pub colors: [PatternComponent; 2],
+    pub rings: f64,
+    pub turbulence: f64,
+    pub octaves: usize,
+    pub persistence: f64,
+    pub transform: Matrix4x4,
+}
+
+impl WoodPattern {
+    pub fn new(color_1: Color, color_2: Color) -> WoodPattern {
+        WoodPattern {
+            colors: [color_1.into(), color_2.into()],
+            rings: 4.0,
+            turbulence: 0.1,
+            octaves: 2,
+            persistence: 0.5,
+            transform: Matrix4x4::new(),
+        }
+    }
+}
+
+impl Patternable for WoodPattern {
+    fn get_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4x4) {
+        self.transform = transform;
+    }
+
+    fn pattern_at(&self, point: Vector4D) -> Color {
+        let noise = turbulence(point, self.octaves, self.persistence);
+        let distance = (point.x.powi(2) + point.z.powi(2)).sqrt() + self.turbulence * noise;
+        let ring = distance * self.rings;
+        let fraction = ring - ring.floor();
+        let color_1 = self.colors[0].color_at(point);
+        let color_2 = self.colors[1].color_at(point);
+        color_1 + (color_2 - color_1) * fraction
+    }
+
+}
+
 // A checkered pattern pattern creates a pattern that interpolates between 2 colores along the
 // x-axis 
 
@@ -277,7 +719,6 @@ impl Patternable for TestPattern {
     }
 
     fn pattern_at(&self, point: Vector4D) -> Color {
-        println!("Requested TestPattern at: {:?}", point);
         Color::new(point.x, point.y, point.z)
     }
 
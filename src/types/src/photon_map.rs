@@ -0,0 +1,225 @@
+use crate::Color;
+use crate::Vector4D;
+use crate::Ray;
+use crate::Shape;
+use crate::World;
+use crate::Sampler;
+use crate::hit;
+use crate::schlick;
+use crate::refraction_direction;
+use crate::sample_cone;
+use crate::bounding_sphere;
+use std::f64::consts::PI;
+
+// A bundle of light that landed on a diffuse surface
+#[derive(Debug, Clone, Copy)]
+pub struct Photon {
+    pub position: Vector4D,
+    // Direction the photon came from, pointing away from the surface
+    pub incoming: Vector4D,
+    pub power: Color,
+}
+
+// Photons that reached diffuse surfaces through glass or mirrors, used to draw caustics. The
+// photons are kept as a balanced kd-tree, the photon in the middle of every range splits it on
+// the axis in axes.
+#[derive(Debug, Clone)]
+pub struct PhotonMap {
+    pub photons: Vec<Photon>,
+    axes: Vec<usize>,
+    // How many of the nearest photons are used to estimate the light at a point, and how far
+    // away they can be
+    pub gather_count: usize,
+    pub max_radius: f64,
+}
+
+fn axis_value(p: Vector4D, axis: usize) -> f64 {
+    match axis {
+        0 => p.x,
+        1 => p.y,
+        _ => p.z,
+    }
+}
+
+impl PhotonMap {
+    pub fn new(photons: Vec<Photon>) -> PhotonMap {
+        let mut map = PhotonMap {
+            axes: vec![0; photons.len()],
+            photons,
+            gather_count: 50,
+            max_radius: 0.5,
+        };
+        let len = map.photons.len();
+        map.build(0, len);
+        map
+    }
+
+    fn build(&mut self, lo: usize, hi: usize) {
+        if hi - lo <= 1 {
+            return;
+        }
+        // split on the axis the photons are most spread out along
+        let mut min = [std::f64::INFINITY; 3];
+        let mut max = [-std::f64::INFINITY; 3];
+        for photon in self.photons[lo..hi].iter() {
+            for axis in 0..3 {
+                let v = axis_value(photon.position, axis);
+                min[axis] = min[axis].min(v);
+                max[axis] = max[axis].max(v);
+            }
+        }
+        let axis = (0..3).max_by(|a, b| (max[*a] - min[*a]).partial_cmp(&(max[*b] - min[*b])).unwrap()).unwrap();
+        let mid = (lo + hi) / 2;
+        self.photons[lo..hi].select_nth_unstable_by(mid - lo, |a, b| {
+            axis_value(a.position, axis).partial_cmp(&axis_value(b.position, axis)).unwrap()
+        });
+        self.axes[mid] = axis;
+        self.build(lo, mid);
+        self.build(mid + 1, hi);
+    }
+
+    // Up to count photons closest to point and within max_radius of it, nearest first
+    pub fn nearest(&self, point: Vector4D, count: usize, max_radius: f64) -> Vec<&Photon> {
+        let mut found: Vec<(f64, usize)> = vec![];
+        self.gather(point, max_radius * max_radius, 0, self.photons.len(), &mut found);
+        found.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        found.truncate(count);
+        found.iter().map(|(_, i)| &self.photons[*i]).collect()
+    }
+
+    fn gather(&self, point: Vector4D, max_dist2: f64, lo: usize, hi: usize, found: &mut Vec<(f64, usize)>) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        let photon = &self.photons[mid];
+        let offset = point - photon.position;
+        let dist2 = offset.x * offset.x + offset.y * offset.y + offset.z * offset.z;
+        if dist2 <= max_dist2 {
+            found.push((dist2, mid));
+        }
+        if hi - lo == 1 {
+            return;
+        }
+        let axis = self.axes[mid];
+        let delta = axis_value(point, axis) - axis_value(photon.position, axis);
+        let (near, far) = if delta < 0.0 { ((lo, mid), (mid + 1, hi)) } else { ((mid + 1, hi), (lo, mid)) };
+        self.gather(point, max_dist2, near.0, near.1, found);
+        if delta * delta <= max_dist2 {
+            self.gather(point, max_dist2, far.0, far.1, found);
+        }
+    }
+
+    // Light arriving at point on a surface facing normal, from the photons around it
+    pub fn irradiance_at(&self, point: Vector4D, normal: Vector4D) -> Color {
+        let nearest = self.nearest(point, self.gather_count, self.max_radius);
+        if nearest.is_empty() {
+            return Color::BLACK;
+        }
+        // the photons are spread over a disc out to the farthest one found
+        let radius = if nearest.len() < self.gather_count {
+            self.max_radius
+        } else {
+            (point - nearest[nearest.len() - 1].position).norm()
+        };
+        let power = nearest.iter()
+            .filter(|photon| photon.incoming.dot(normal) > 0.0)
+            .fold(Color::BLACK, |acc, photon| acc + photon.power);
+        power * (1.0 / (PI * radius * radius))
+    }
+
+    // Shoot photons from the light source at the mirrors and glass in the world, and keep the
+    // ones that land on diffuse surfaces after bouncing off or through them.
+    pub fn trace(world: &World, photon_count: usize, max_bounces: usize) -> PhotonMap {
+        let targets: Vec<(Vector4D, f64)> = world.objects.iter()
+            .filter(|o| bends_light(o))
+            .filter_map(bounding_sphere)
+            .collect();
+        let light = world.light_source;
+        let mut photons = vec![];
+        let mut sampler = Sampler::new(photon_count as u64);
+        if targets.is_empty() {
+            return PhotonMap::new(photons);
+        }
+        let per_target = photon_count / targets.len();
+        for (center, radius) in targets.iter() {
+            let to_center = *center - light.position;
+            let distance = to_center.norm();
+            if distance <= *radius {
+                continue;
+            }
+            let sin_max = radius / distance;
+            let half_angle = sin_max.asin();
+            let solid_angle = 2.0 * PI * (1.0 - (1.0 - sin_max * sin_max).sqrt());
+            for _ in 0..per_target {
+                let dir = sample_cone(to_center, half_angle, sampler.next_f64(), sampler.next_f64());
+                let ray = Ray::new(light.position, dir);
+                let power = light.intensity * (PI * solid_angle / per_target as f64);
+                if let Some(photon) = trace_photon(world, ray, power, max_bounces, &mut sampler) {
+                    photons.push(photon);
+                }
+            }
+        }
+        PhotonMap::new(photons)
+    }
+}
+
+// Mirrors and glass, a group when any shape in it is
+fn bends_light(shape: &Shape) -> bool {
+    match *shape {
+        Shape::Group(ref group) => group.children.iter().any(bends_light),
+        _ => {
+            let material = shape.get_material();
+            material.reflective > 0.0 || material.transparency > 0.0
+        },
+    }
+}
+
+// Follow a photon through mirrors and glass until it lands on a diffuse surface
+fn trace_photon(world: &World, mut ray: Ray, mut power: Color, max_bounces: usize, sampler: &mut Sampler) -> Option<Photon> {
+    let mut medium: Option<Box<Shape>> = None;
+    for bounce in 0..=max_bounces {
+        let xs = ray.intersect_world(world);
+        let hit = hit(&xs)?;
+        let distance = hit.t * ray.dir().norm();
+        if bounce == 0 {
+            // Lights don't get dimmer with distance in this renderer, so the photons carry the
+            // light that reaches the first thing they hit
+            power = power * (distance * distance);
+        }
+        if let Some(ref medium) = medium {
+            power = power * medium.get_material().transmittance(distance);
+        }
+        let sc = ray.prepare_computations(&hit, &xs);
+        let material = sc.obj.get_material();
+        if bounce > 0 && material.diffuse > 0.0 && material.transparency == 0.0 {
+            return Some(Photon {
+                position: sc.point,
+                incoming: sc.eyev,
+                power,
+            });
+        }
+
+        let (reflect_weight, refract_weight) = if material.reflective > 0.0 && material.transparency > 0.0 {
+            let reflectance = schlick(&sc);
+            (reflectance, (1.0 - reflectance) * material.transparency)
+        } else {
+            (material.reflective, material.transparency)
+        };
+        // Russian roulette, the photon keeps its power if it survives
+        let scale = (reflect_weight + refract_weight).max(1.0);
+        let choice = sampler.next_f64() * scale;
+        if choice < reflect_weight {
+            ray = Ray::new(sc.over_point, sc.reflectv);
+            medium = sc.n1_medium.clone();
+        } else if choice < reflect_weight + refract_weight {
+            let dir = refraction_direction(&sc, sc.n1, sc.n2)?;
+            ray = Ray::new(sc.under_point, dir);
+            medium = sc.n2_medium.clone();
+        } else {
+            return None;
+        }
+        power = power * scale;
+    }
+    None
+}
@@ -9,15 +9,23 @@ use crate::World;
 use crate::Shape;
 use crate::Color;
 use crate::lighting;
-use crate::hit;
+use crate::hit_object;
 use crate::reflect;
 use crate::refractive_indices;
+use crate::integrate_media;
+use crate::record_ray_start;
+use crate::record_ray_end;
+use crate::record_hit;
+use crate::record_shading;
 
 use utils::*;
 #[derive(Debug, Copy, Clone)]
 pub struct Ray {
     pub origin: Vector4D,
-    pub direction: Vector4D
+    pub direction: Vector4D,
+    // Set once a dispersive surface has split the light, the ray then only carries the color
+    // of this wavelength. None is white light.
+    pub wavelength: Option<f64>,
 }
 
 impl Ray {
@@ -25,6 +33,7 @@ impl Ray {
         Ray {
             origin,
             direction,
+            wavelength: None,
         }
     }
 
@@ -50,6 +59,16 @@ impl Ray {
         world.intersect(self)
     }
 
+    // Computations for the first thing the ray hits in world, None when it hits nothing
+    pub fn hit_world(&self, world: &World) -> Option<ShadeComputation> {
+        let objects = world.intersect_objects(self);
+        let (object, hit) = hit_object(&objects)?;
+        let xs: Intersections = objects.into_iter().map(|(_, x)| x).collect();
+        let mut sc = self.prepare_computations(&hit, &xs);
+        sc.object = Some(object);
+        Some(sc)
+    }
+
     // Given an intersection at an object for the ray, compute various things needed for rendering
     // the object, 
     // the normal at the point of intersection
@@ -61,39 +80,12 @@ impl Ray {
     pub fn prepare_computations(&self, intersection: &Intersection, xs: &Intersections) -> ShadeComputation {
         let p = self.at_t(intersection.t);
         let eyev = -self.dir();
-        let mut normalv;
-        let obj;
+        let mut normalv = match intersection.obj.get_material().bump {
+            Some(ref bump) => bump.normal_at_object(intersection.obj.as_intersectable(), p),
+            None => intersection.obj.normal_at(p),
+        };
+        let obj = (*intersection.obj).clone();
         let inside;
-        match &*intersection.obj {
-            Shape::Sphere(sph) => {
-                normalv = sph.normal_at(p);
-                obj = Shape::Sphere(sph.clone());
-            },
-            Shape::TestShape(t) => {
-                normalv = t.normal_at(p);
-                obj = Shape::TestShape(t.clone());
-            }
-            Shape::Plane(plane) => {
-                normalv = plane.normal_at(p);
-                obj = Shape::Plane(plane.clone());
-            },
-            Shape::Cube(cube) => {
-                normalv = cube.normal_at(p);
-                obj = Shape::Cube(cube.clone());
-            },
-            Shape::Cylinder(o) => {
-                normalv = o.normal_at(p);
-                obj = Shape::Cylinder(o.clone());
-            },
-            Shape::Cone(o) => {
-                normalv = o.normal_at(p);
-                obj = Shape::Cone(o.clone());
-            },
-            Shape::Group(o) => {
-                normalv = o.normal_at(p);
-                obj = Shape::Group(o.clone());
-            },
-        }
 
         if normalv.dot(eyev) < 0.0 {
             inside = true;
@@ -106,6 +98,8 @@ impl Ray {
         // compute the n1 n2 refraction values
         let mut refract_n1 = refractive_indices::VACUUM;
         let mut refract_n2 = refractive_indices::VACUUM;
+        let mut n1_medium = None;
+        let mut n2_medium = None;
         let hit : &Intersection = intersection;
         let mut containers: Vec<Box<Shape>> = vec![];
         for  i in xs.iter() {
@@ -116,6 +110,7 @@ impl Ray {
                     refract_n1 = refractive_indices::VACUUM; 
                 } else {
                     refract_n1 = containers.last().unwrap().get_material().refractive_index; 
+                    n1_medium = containers.last().cloned();
                 }
             }
 
@@ -134,6 +129,7 @@ impl Ray {
                     refract_n2 = refractive_indices::VACUUM;
                 } else {
                     refract_n2 = containers.last().unwrap().get_material().refractive_index;
+                    n2_medium = containers.last().cloned();
                 }
                 break;
             }
@@ -155,7 +151,10 @@ impl Ray {
             reflectv: reflect(self.dir(), normalv),
             n1: refract_n1,
             n2: refract_n2, 
-            do_debug: None,
+            n1_medium,
+            n2_medium,
+            object: None,
+            wavelength: self.wavelength,
         };
         sc
         
@@ -164,12 +163,13 @@ impl Ray {
     pub fn transform(&self, m: &Matrix4x4) -> Ray {
         Ray {
             origin: m.mul_vector4d(&self.origin),
-            direction: m.mul_vector4d(&self.direction)
+            direction: m.mul_vector4d(&self.direction),
+            wavelength: self.wavelength,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ShadeComputation {
     pub t: f64,
     pub obj: Box<Shape>,
@@ -182,32 +182,30 @@ pub struct ShadeComputation {
     pub reflectv: Vector4D,
     pub n1: f64, // Refraction index 1
     pub n2: f64, // Refraction index 2
-    // Debug
-    pub do_debug : Option<bool>
+    // The objects the ray is inside of on either side of the surface, reflected rays travel
+    // through n1_medium and refracted rays through n2_medium. None is empty space.
+    pub n1_medium: Option<Box<Shape>>,
+    pub n2_medium: Option<Box<Shape>>,
+    // Index in World::objects of the object hit, for a shape in a group it's the group. Only
+    // known to computations made by Ray::hit_world.
+    pub object: Option<usize>,
+    // Wavelength of the ray that hit, see Ray::wavelength
+    pub wavelength: Option<f64>,
 }
-pub static mut global_do_debug: Option<bool> = None;
 
 pub fn shade_hit(world: &World, sc: &ShadeComputation, reflect_rays_remaining: usize) -> Color {
-    unsafe {
-    if let Some(_) = sc.do_debug {
-        global_do_debug = Some(true);
-    }
+    let mut material = sc.obj.get_material();
+    if let Some(ref occlusion) = world.ambient_occlusion {
+        material.ambient *= occlusion.visibility(world, sc.over_point, sc.normalv);
     }
-    let surface =  lighting(sc.obj.get_material(), 
+    let surface =  lighting(material, 
                             &*sc.obj, 
                             world.light_source,
                             sc.over_point, sc.eyev, sc.normalv, world.is_shadowed(sc.over_point));
+    let surface = surface + sc.obj.get_material().emissive + world.area_light(sc) + world.caustics(sc);
     let reflected = world.reflected_color(sc, reflect_rays_remaining);
     let refracted = world.refracted_color(sc, reflect_rays_remaining);
-    if let Some(_) = sc.do_debug {
-    println!("shade_hit: {} surface color: {:?} reflected color: {:?}\nrefracted color: {:?}",
-             reflect_rays_remaining, surface, reflected, refracted);
-    }
-
-    //DEBUG , clear debug 
-    unsafe {
-    global_do_debug = None;
-    }
+    record_shading(surface, reflected, refracted);
     let mut m  = sc.obj.get_material();
     if m.reflective > 0.0 && m.transparency > 0.0 {
         let reflectance = schlick(&sc);
@@ -219,34 +217,22 @@ pub fn shade_hit(world: &World, sc: &ShadeComputation, reflect_rays_remaining: u
 }
 
 pub fn color_at(world: &World, ray: Ray, remaining: usize) -> Color {
-    let xs = ray.intersect_world(world);
-    let mut do_debug = false;
-    // Debug
-    if ray.origin().x == 0.0 && ray.origin().y == 5.0 && ray.origin().z == 0.0 && 
-        f64_eq(ray.dir().x, -0.48060956)  && f64_eq(ray.dir().y, -0.8408486) && f64_eq(ray.dir().z, 0.2489737) {
-            println!("bad ray: {:?}\nxs{:?}", ray, xs);
-            do_debug = true;
-    }
-    if let Some(hit) = hit(&xs) {
-        let mut sc = ray.prepare_computations(&hit, &xs);
-        if do_debug {
-            sc.do_debug = Some(true);
-            println!("sc:{:?}", sc);
-        }
-        //DEBUG
+    color_and_hit_at(world, ray, remaining).0
+}
+
+// Like color_at, also returns the computations of the hit that was shaded, None when the ray
+// sees the background
+pub fn color_and_hit_at(world: &World, ray: Ray, remaining: usize) -> (Color, Option<ShadeComputation>) {
+    record_ray_start(&ray, remaining);
+    let (color, sc) = if let Some(sc) = ray.hit_world(world) {
+        record_hit(&sc);
         let color = shade_hit(world, &sc, remaining);
-        if ray.origin().x == 0.0 && ray.origin().y == 5.0 && ray.origin().z == 0.0 && 
-            f64_eq(ray.dir().x, -0.48060956)  && f64_eq(ray.dir().y, -0.8408486) && f64_eq(ray.dir().z, 0.2489737) {
-                println!("bad ray: {:?}\n color: {:?}", ray, color);
-        }
-        color
+        (integrate_media(world, &ray, Some(sc.t), color), Some(sc))
     } else {
-            if ray.origin().x == 0.0 && ray.origin().y == 5.0 && ray.origin().z == 0.0 && 
-                f64_eq(ray.dir().x, -0.48060956)  && f64_eq(ray.dir().y, -0.8408486) && f64_eq(ray.dir().z, 0.2489737) {
-                println!("bad ray: {:?}\n color black", ray);
-            }
-        Color::BLACK
-    }
+        (integrate_media(world, &ray, None, world.background.color_at(ray.dir())), None)
+    };
+    record_ray_end(color);
+    (color, sc)
 }
 
 pub fn schlick(sc: &ShadeComputation) -> f64 {
@@ -0,0 +1,275 @@
+use crate::Color;
+use crate::Vector4D;
+use crate::Ray;
+use crate::ShadeComputation;
+use crate::World;
+use crate::color_at;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::Write;
+
+// Why a ray was traced
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RayKind {
+    Camera,
+    Reflection,
+    Refraction,
+}
+
+// What a ray hit
+#[derive(Debug, Clone)]
+pub struct HitRecord {
+    pub t: f64,
+    // Shape::type_name of the object
+    pub object: &'static str,
+    pub point: Vector4D,
+    pub eyev: Vector4D,
+    pub normalv: Vector4D,
+    pub inside: bool,
+    pub n1: f64,
+    pub n2: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShadowTest {
+    pub point: Vector4D,
+    pub light_distance: f64,
+    // Distance to the first thing the shadow ray hit
+    pub blocker_t: Option<f64>,
+    pub shadowed: bool,
+}
+
+// Everything that happened while finding the color seen along a ray, with the rays it spawned
+// as children
+#[derive(Debug, Clone)]
+pub struct RayRecord {
+    pub kind: RayKind,
+    // Reflections and refractions left
+    pub remaining: usize,
+    pub origin: Vector4D,
+    pub direction: Vector4D,
+    pub hit: Option<HitRecord>,
+    pub shadow_tests: Vec<ShadowTest>,
+    // The parts shade_hit added up for the hit
+    pub surface: Option<Color>,
+    pub reflected: Option<Color>,
+    pub refracted: Option<Color>,
+    pub color: Color,
+    pub children: Vec<RayRecord>,
+}
+
+// The rays being traced, innermost last, each with the kind its children get, and the finished
+// top level rays
+struct Recorder {
+    stack: Vec<(RayRecord, RayKind)>,
+    finished: Vec<RayRecord>,
+}
+
+// Like the ray stats the recorder is per thread, and the hooks do nothing unless it's on.
+// RECORDING mirrors whether RECORDER is set, so a hook only tests a flag when it's off.
+thread_local! {
+    static RECORDER: RefCell<Option<Recorder>> = const { RefCell::new(None) };
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+}
+
+// Starts recording every ray color_at traces on this thread
+pub fn start_recording_rays() {
+    RECORDER.with(|recorder| *recorder.borrow_mut() = Some(Recorder { stack: vec![], finished: vec![] }));
+    RECORDING.with(|recording| recording.set(true));
+}
+
+// Stops recording and returns the top level rays recorded
+pub fn stop_recording_rays() -> Vec<RayRecord> {
+    RECORDING.with(|recording| recording.set(false));
+    RECORDER.with(|recorder| recorder.borrow_mut().take().map_or(vec![], |r| r.finished))
+}
+
+// The top level rays recorded so far, recording carries on
+pub fn take_recorded_rays() -> Vec<RayRecord> {
+    RECORDER.with(|recorder| recorder.borrow_mut().as_mut().map_or(vec![], |r| std::mem::take(&mut r.finished)))
+}
+
+pub fn recording_rays() -> bool {
+    RECORDING.with(|recording| recording.get())
+}
+
+// The record of color_at for ray, to see how a color came about
+pub fn record_ray(world: &World, ray: Ray, remaining: usize) -> RayRecord {
+    start_recording_rays();
+    color_at(world, ray, remaining);
+    stop_recording_rays().remove(0)
+}
+
+#[inline]
+fn with_recorder<F: FnOnce(&mut Recorder)>(f: F) {
+    if !recording_rays() {
+        return;
+    }
+    RECORDER.with(|recorder| {
+        if let Some(ref mut recorder) = *recorder.borrow_mut() {
+            f(recorder);
+        }
+    });
+}
+
+fn with_current<F: FnOnce(&mut RayRecord)>(f: F) {
+    with_recorder(|recorder| {
+        if let Some((current, _)) = recorder.stack.last_mut() {
+            f(current);
+        }
+    });
+}
+
+pub(crate) fn record_ray_start(ray: &Ray, remaining: usize) {
+    with_recorder(|recorder| {
+        let kind = recorder.stack.last().map_or(RayKind::Camera, |(_, child_kind)| *child_kind);
+        let record = RayRecord {
+            kind,
+            remaining,
+            origin: ray.origin(),
+            direction: ray.dir(),
+            hit: None,
+            shadow_tests: vec![],
+            surface: None,
+            reflected: None,
+            refracted: None,
+            color: Color::BLACK,
+            children: vec![],
+        };
+        recorder.stack.push((record, RayKind::Reflection));
+    });
+}
+
+pub(crate) fn record_ray_end(color: Color) {
+    with_recorder(|recorder| {
+        if let Some((mut record, _)) = recorder.stack.pop() {
+            record.color = color;
+            match recorder.stack.last_mut() {
+                Some((parent, _)) => parent.children.push(record),
+                None => recorder.finished.push(record),
+            }
+        }
+    });
+}
+
+// The kind of the rays the current ray traces next
+pub(crate) fn record_next_ray_kind(kind: RayKind) {
+    with_recorder(|recorder| {
+        if let Some((_, child_kind)) = recorder.stack.last_mut() {
+            *child_kind = kind;
+        }
+    });
+}
+
+pub(crate) fn record_hit(sc: &ShadeComputation) {
+    with_current(|current| current.hit = Some(HitRecord {
+        t: sc.t,
+        object: sc.obj.type_name(),
+        point: sc.point,
+        eyev: sc.eyev,
+        normalv: sc.normalv,
+        inside: sc.inside,
+        n1: sc.n1,
+        n2: sc.n2,
+    }));
+}
+
+pub(crate) fn record_shadow_test(point: Vector4D, light_distance: f64, blocker_t: Option<f64>, shadowed: bool) {
+    with_current(|current| current.shadow_tests.push(ShadowTest {
+        point,
+        light_distance,
+        blocker_t,
+        shadowed,
+    }));
+}
+
+pub(crate) fn record_shading(surface: Color, reflected: Color, refracted: Color) {
+    with_current(|current| {
+        current.surface = Some(surface);
+        current.reflected = Some(reflected);
+        current.refracted = Some(refracted);
+    });
+}
+
+fn vector_text(v: Vector4D) -> String {
+    format!("({:.5}, {:.5}, {:.5})", v.x, v.y, v.z)
+}
+
+fn color_text(c: Color) -> String {
+    format!("({:.5}, {:.5}, {:.5})", c.red, c.green, c.blue)
+}
+
+fn json_number(v: f64) -> String {
+    if v.is_finite() { format!("{}", v) } else { "null".to_string() }
+}
+
+fn json_vector(v: Vector4D) -> String {
+    format!("[{}, {}, {}]", json_number(v.x), json_number(v.y), json_number(v.z))
+}
+
+fn json_color(c: Color) -> String {
+    format!("[{}, {}, {}]", json_number(c.red), json_number(c.green), json_number(c.blue))
+}
+
+fn json_option<T, F: Fn(T) -> String>(v: Option<T>, f: F) -> String {
+    v.map_or("null".to_string(), f)
+}
+
+impl RayRecord {
+    fn write_text(&self, out: &mut String, indent: usize) {
+        let pad = "  ".repeat(indent);
+        let _ = writeln!(out, "{}{:?} ray, {} remaining, origin {} direction {}", pad, self.kind, self.remaining,
+                         vector_text(self.origin), vector_text(self.direction));
+        match self.hit {
+            Some(ref hit) => {
+                let _ = writeln!(out, "{}  hit {} at t {:.5}, point {} normal {} eye {}{}, n1 {} n2 {}", pad, hit.object, hit.t,
+                                 vector_text(hit.point), vector_text(hit.normalv), vector_text(hit.eyev),
+                                 if hit.inside { " inside" } else { "" }, hit.n1, hit.n2);
+            },
+            None => { let _ = writeln!(out, "{}  miss", pad); },
+        }
+        for test in self.shadow_tests.iter() {
+            let _ = writeln!(out, "{}  shadow test from {}, light at {:.5}, {}", pad, vector_text(test.point), test.light_distance,
+                             match test.blocker_t {
+                                 Some(t) if test.shadowed => format!("shadowed by a hit at {:.5}", t),
+                                 Some(t) => format!("lit, first hit at {:.5}", t),
+                                 None => "lit".to_string(),
+                             });
+        }
+        if let (Some(surface), Some(reflected), Some(refracted)) = (self.surface, self.reflected, self.refracted) {
+            let _ = writeln!(out, "{}  surface {} reflected {} refracted {}", pad,
+                             color_text(surface), color_text(reflected), color_text(refracted));
+        }
+        let _ = writeln!(out, "{}  color {}", pad, color_text(self.color));
+        for child in self.children.iter() {
+            child.write_text(out, indent + 1);
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let hit = json_option(self.hit.as_ref(), |hit| format!(
+            "{{\"t\": {}, \"object\": \"{}\", \"point\": {}, \"eyev\": {}, \"normalv\": {}, \"inside\": {}, \"n1\": {}, \"n2\": {}}}",
+            json_number(hit.t), hit.object, json_vector(hit.point), json_vector(hit.eyev), json_vector(hit.normalv),
+            hit.inside, json_number(hit.n1), json_number(hit.n2)));
+        let shadow_tests: Vec<String> = self.shadow_tests.iter().map(|test| format!(
+            "{{\"point\": {}, \"light_distance\": {}, \"blocker_t\": {}, \"shadowed\": {}}}",
+            json_vector(test.point), json_number(test.light_distance), json_option(test.blocker_t, json_number), test.shadowed))
+            .collect();
+        let children: Vec<String> = self.children.iter().map(|child| child.to_json()).collect();
+        format!("{{\"kind\": \"{:?}\", \"remaining\": {}, \"origin\": {}, \"direction\": {}, \"hit\": {}, \"shadow_tests\": [{}], \
+                 \"surface\": {}, \"reflected\": {}, \"refracted\": {}, \"color\": {}, \"children\": [{}]}}",
+                self.kind, self.remaining, json_vector(self.origin), json_vector(self.direction), hit, shadow_tests.join(", "),
+                json_option(self.surface, json_color), json_option(self.reflected, json_color),
+                json_option(self.refracted, json_color), json_color(self.color), children.join(", "))
+    }
+}
+
+// An indented tree, a ray per line followed by what happened to it
+impl fmt::Display for RayRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = String::new();
+        self.write_text(&mut out, 0);
+        write!(f, "{}", out)
+    }
+}
@@ -0,0 +1,83 @@
+use crate::Vector4D;
+use std::f64::consts::PI;
+
+// A small xorshift random number generator for Monte Carlo sampling. It's seeded from the
+// ray being shaded so renders are the same every time and no state has to be threaded
+// through the renderer.
+#[derive(Debug, Clone, Copy)]
+pub struct Sampler {
+    state: u64,
+}
+
+impl Sampler {
+    pub fn new(seed: u64) -> Sampler {
+        // mix the seed so nearby seeds don't give similar sequences, and xorshift can't
+        // start from 0
+        let mut sampler = Sampler { state: seed ^ 0x9e37_79b9_7f4a_7c15 };
+        sampler.state = sampler.mix();
+        if sampler.state == 0 {
+            sampler.state = 0x9e37_79b9_7f4a_7c15;
+        }
+        sampler
+    }
+
+    pub fn from_vectors(a: Vector4D, b: Vector4D) -> Sampler {
+        let mut seed = 0u64;
+        for c in [a.x, a.y, a.z, b.x, b.y, b.z].iter() {
+            seed = seed.rotate_left(7) ^ c.to_bits();
+        }
+        Sampler::new(seed)
+    }
+
+    fn mix(&self) -> u64 {
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // Uniform in 0.0..1.0
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Two unit vectors perpendicular to n and each other
+pub fn orthonormal_basis(n: Vector4D) -> (Vector4D, Vector4D) {
+    let helper = if n.x.abs() > 0.9 {
+        Vector4D::new_vector(0.0, 1.0, 0.0)
+    } else {
+        Vector4D::new_vector(1.0, 0.0, 0.0)
+    };
+    let tangent = helper.cross(n).normalized();
+    let bitangent = n.cross(tangent);
+    (tangent, bitangent)
+}
+
+// A direction uniformly distributed in the cone around axis with the given half angle, u1 and
+// u2 are uniform random numbers.
+pub fn sample_cone(axis: Vector4D, half_angle: f64, u1: f64, u2: f64) -> Vector4D {
+    let axis = axis.normalized();
+    let cos_max = half_angle.cos();
+    let cos_theta = 1.0 - u1 * (1.0 - cos_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    let (tangent, bitangent) = orthonormal_basis(axis);
+    (sin_theta * phi.cos()) * tangent + (sin_theta * phi.sin()) * bitangent + cos_theta * axis
+}
+
+// A direction in the hemisphere around normal, more likely close to the normal (pdf is
+// cos(theta) / PI), u1 and u2 are uniform random numbers.
+pub fn sample_cosine_hemisphere(normal: Vector4D, u1: f64, u2: f64) -> Vector4D {
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    (r * phi.cos()) * tangent + (r * phi.sin()) * bitangent + (1.0 - u1).max(0.0).sqrt() * normal
+}
@@ -4,9 +4,10 @@ use crate::Vector4D;
 use crate::Matrix4x4;
 use crate::Ray;
 use crate::Material;
-use crate::global_do_debug;
 use crate::Bounds;
+use crate::record_stats;
 use std::collections::HashMap;
+use std::fmt::Debug;
 
 extern crate lazy_static;
 use lazy_static::lazy_static;
@@ -20,281 +21,107 @@ pub enum Shape {
     Cylinder(Cylinder),
     Cone(Cone),
     Group(Group), 
+    // A shape defined outside of the types crate
+    Custom(BoxedShape),
 }
 
 impl Shape {
 
-    pub fn intersect(&self, ray: &Ray) -> Intersections {
-        match *self {
-            Shape::Sphere(ref o) => {
-                o.intersect(ray)
-            },
-            Shape::TestShape(ref o) => {
-               o.intersect(ray) 
-            },
-            Shape::Plane(ref o) => {
-               o.intersect(ray)
-            },
-            Shape::Cube(ref o) => {
-               o.intersect(ray)
-            },
-            Shape::Cylinder(ref o) => {
-               o.intersect(ray)
-            },
-            Shape::Cone(ref o) => {
-               o.intersect(ray)
-            },
-            Shape::Group(ref o) => {
-               o.intersect(ray)
-            },
-        }
+    pub fn custom<S: 'static + Intersectable + Clone + Send + Sync>(shape: S) -> Shape {
+        Shape::Custom(Box::new(shape))
     }
 
-    pub fn eq(&self, other: &Shape) -> bool {
+    pub fn as_intersectable(&self) -> &dyn Intersectable {
         match *self {
-            Shape::Sphere(ref s) => {
-                s.eq(&other)
-            },
-            Shape::TestShape(ref t) => {
-               t.eq(&other) 
-            },
-            Shape::Plane(ref p) => {
-                p.eq(&other)
-            },
-            Shape::Cube(ref c) => {
-                c.eq(&other)
-            },
-            Shape::Cylinder(ref c) => {
-                c.eq(&other)
-            },
-            Shape::Cone(ref c) => {
-                c.eq(&other)
-            },
-            Shape::Group(ref g) => {
-                g.eq(&other)
-            },
+            Shape::Sphere(ref o) => o,
+            Shape::TestShape(ref o) => o,
+            Shape::Plane(ref o) => o,
+            Shape::Cube(ref o) => o,
+            Shape::Cylinder(ref o) => o,
+            Shape::Cone(ref o) => o,
+            Shape::Group(ref o) => o,
+            Shape::Custom(ref o) => o.as_ref(),
         }
     }
 
-    pub fn get_material(&self) -> Material {
+    pub fn as_intersectable_mut(&mut self) -> &mut dyn Intersectable {
         match *self {
-            Shape::Sphere(ref s) => {
-                s.get_material()
-            },
-            Shape::TestShape(ref t) => {
-                t.get_material()
-            },
-            Shape::Plane(ref p) => {
-                p.get_material()
-            },
-            Shape::Cube(ref c) => {
-                c.get_material()
-            },
-            Shape::Cylinder(ref c) => {
-                c.get_material()
-            },
-            Shape::Cone(ref c) => {
-                c.get_material()
-            },
-            // FIXME: a group does not have a material
-            Shape::Group(ref g) => {
-                Default::default()
-            },
+            Shape::Sphere(ref mut o) => o,
+            Shape::TestShape(ref mut o) => o,
+            Shape::Plane(ref mut o) => o,
+            Shape::Cube(ref mut o) => o,
+            Shape::Cylinder(ref mut o) => o,
+            Shape::Cone(ref mut o) => o,
+            Shape::Group(ref mut o) => o,
+            Shape::Custom(ref mut o) => o.as_mut(),
         }
     }
 
-    pub fn set_material(&mut self, material: &Material) {
+    pub fn intersect(&self, ray: &Ray) -> Intersections {
+        record_stats(|stats| *stats.shape_tests.entry(self.type_name()).or_insert(0) += 1);
+        self.as_intersectable().intersect(ray)
+    }
+
+    pub fn type_name(&self) -> &'static str {
         match *self {
-            Shape::Sphere(ref mut s) => {
-                s.set_material(material.clone())
-            },
-            Shape::TestShape(ref mut t) => {
-                t.set_material(material.clone())
-            },
-            Shape::Plane(ref mut p) => {
-                p.set_material(material.clone())
-            },
-            Shape::Cube(ref mut c) => {
-                c.set_material(material.clone())
-            },
-            Shape::Cylinder(ref mut c) => {
-                c.set_material(material.clone())
-            },
-            Shape::Cone(ref mut c) => {
-                c.set_material(material.clone())
-            },
-            // FIXME: a group does not have a material
-            Shape::Group(ref mut g) => {
-                //g.set_material(material.clone())
-            },
+            Shape::Sphere(_) => "sphere",
+            Shape::TestShape(_) => "test shape",
+            Shape::Plane(_) => "plane",
+            Shape::Cube(_) => "cube",
+            Shape::Cylinder(_) => "cylinder",
+            Shape::Cone(_) => "cone",
+            Shape::Group(_) => "group",
+            Shape::Custom(_) => "custom",
         }
     }
 
+    pub fn eq(&self, other: &Shape) -> bool {
+        self.as_intersectable().eq(other)
+    }
+
+    pub fn get_material(&self) -> Material {
+        self.as_intersectable().get_material()
+    }
+
+    pub fn set_material(&mut self, material: &Material) {
+        self.as_intersectable_mut().set_material(material.clone())
+    }
 
     pub fn normal_at(&self, point: Vector4D) -> Vector4D {
-        match *self {
-            Shape::Sphere(ref o) => {
-                o.normal_at(point)
-            },
-            Shape::TestShape(ref o) => {
-                o.normal_at(point)
-            },
-            Shape::Plane(ref o) => {
-                o.normal_at(point)
-            },
-            Shape::Cube(ref o) => {
-                o.normal_at(point)
-            },
-            Shape::Cylinder(ref o) => {
-                o.normal_at(point)
-            },
-            Shape::Cone(ref o) => {
-                o.normal_at(point)
-            },
-            Shape::Group(ref o) => {
-                o.normal_at(point)
-            },
-        }
+        self.as_intersectable().normal_at(point)
     }
 
     pub fn set_parent(&mut self, parent: Group)  {  
-        println!("set_parent: {:?}", parent);
-        match *self {
-            Shape::Sphere(ref mut o) => {
-                o.set_parent(parent)
-            },
-            Shape::TestShape(ref mut o) => {
-                o.set_parent(parent)
-            },
-            Shape::Plane(ref mut o) => {
-                o.set_parent(parent)
-            },
-            Shape::Cube(ref mut o) => {
-                o.set_parent(parent)
-            },
-            Shape::Cylinder(ref mut o) => {
-                o.set_parent(parent)
-            },
-            Shape::Cone(ref mut o) => {
-                o.set_parent(parent)
-            },
-            Shape::Group(ref mut o) => {
-                o.set_parent(parent)
-            },
-        }
+        self.as_intersectable_mut().set_parent(parent)
     }
 
-
     pub fn get_parent(&self) -> Option<Group> {
-        match *self {
-            Shape::Sphere(ref o) => {
-                o.get_parent()
-            },
-            Shape::TestShape(ref o) => {
-                o.get_parent()
-            },
-            Shape::Plane(ref o) => {
-                o.get_parent()
-            },
-            Shape::Cube(ref o) => {
-                o.get_parent()
-            },
-            Shape::Cylinder(ref o) => {
-                o.get_parent()
-            },
-            Shape::Cone(ref o) => {
-                o.get_parent()
-            },
-            Shape::Group(ref o) => {
-                o.get_parent()
-            },
-        }
+        self.as_intersectable().get_parent()
     }
 
     pub fn set_transform(&mut self, transform: Matrix4x4) {
-        match *self {
-            Shape::Sphere(ref mut o) => {
-                o.set_transform(transform)
-            },
-            Shape::TestShape(ref mut o) => {
-                o.set_transform(transform)
-            },
-            Shape::Plane(ref mut o) => {
-                o.set_transform(transform)
-            },
-            Shape::Cube(ref mut o) => {
-                o.set_transform(transform)
-            },
-            Shape::Cylinder(ref mut o) => {
-                o.set_transform(transform)
-            },
-            Shape::Cone(ref mut o) => {
-                o.set_transform(transform)
-            },
-            Shape::Group(ref mut o) => {
-                o.set_transform(transform)
-            },
-        }
+        self.as_intersectable_mut().set_transform(transform)
     }
 
-
     pub fn world_to_object(&self, world_point: Vector4D) -> Vector4D {
-        match *self {
-            Shape::Sphere(ref o) => {
-                o.world_to_object(world_point)
-            },
-            Shape::TestShape(ref o) => {
-                o.world_to_object(world_point)
-            },
-            Shape::Plane(ref o) => {
-                o.world_to_object(world_point)
-            },
-            Shape::Cube(ref o) => {
-                o.world_to_object(world_point)
-            },
-            Shape::Cylinder(ref o) => {
-                o.world_to_object(world_point)
-            },
-            Shape::Cone(ref o) => {
-                o.world_to_object(world_point)
-            },
-            Shape::Group(ref o) => {
-                o.world_to_object(world_point)
-            },
-        }
+        self.as_intersectable().world_to_object(world_point)
     }
 
     pub fn normal_to_world(&self, world_point: Vector4D) -> Vector4D {
-        match *self {
-            Shape::Sphere(ref o) => {
-                o.normal_to_world(world_point)
-            },
-            Shape::TestShape(ref o) => {
-                o.normal_to_world(world_point)
-            },
-            Shape::Plane(ref o) => {
-                o.normal_to_world(world_point)
-            },
-            Shape::Cube(ref o) => {
-                o.normal_to_world(world_point)
-            },
-            Shape::Cylinder(ref o) => {
-                o.normal_to_world(world_point)
-            },
-            Shape::Cone(ref o) => {
-                o.normal_to_world(world_point)
-            },
-            Shape::Group(ref o) => {
-                o.normal_to_world(world_point)
-            },
-        }
+        self.as_intersectable().normal_to_world(world_point)
     }
 }
 
 #[derive(Debug,  Clone)]
 pub struct Intersection {
     pub obj: Box<Shape>,
-    pub t: f64
+    pub t: f64,
+}
+
+impl Intersection {
+    pub fn new(t: f64, obj: Shape) -> Intersection {
+        Intersection { obj: Box::new(obj), t }
+    }
 }
 
 pub type Intersections = Vec<Intersection>;
@@ -310,6 +137,14 @@ pub fn hit(xs: &Intersections) -> Option<Intersection> {
     None
 }
 
+// hit for intersections paired with the index of their object, as World::intersect_objects
+// returns them
+pub fn hit_object(xs: &[(usize, Intersection)]) -> Option<(usize, Intersection)> {
+    xs.iter().filter(|(_, x)| x.t >= 0.0)
+        .min_by(|(_, a), (_, b)| a.t.partial_cmp(&b.t).unwrap())
+        .cloned()
+}
+
 pub fn positive_hits(xs: &Intersections) -> Intersections {
     let mut s = xs.clone();
     s.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
@@ -344,7 +179,27 @@ fn check_axis(origin: f64, direction: f64) -> TMinMax {
    }
 }
 
-pub trait Intersectable {
+// Shapes defined outside of the types crate are stored as trait objects, anything that is
+// Intersectable + Clone + Send + Sync can be boxed up with Shape::custom.
+pub type BoxedShape = Box<dyn Intersectable + Send + Sync>;
+
+pub trait ShapeClone {
+    fn clone_shape(&self) -> BoxedShape;
+}
+
+impl<T> ShapeClone for T where T: 'static + Intersectable + Clone + Send + Sync {
+    fn clone_shape(&self) -> BoxedShape {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for BoxedShape {
+    fn clone(&self) -> Self {
+        self.clone_shape()
+    }
+}
+
+pub trait Intersectable: ShapeClone + Debug {
     fn intersect(&self, ray: &Ray) -> Intersections;
     fn eq(&self, other: &Shape) -> bool;
     fn set_transform(&mut self, m: Matrix4x4);
@@ -375,7 +230,6 @@ pub trait Intersectable {
     fn normal_at_local(&self, obj_point: Vector4D) -> Vector4D;
 
     fn normal_to_world(&self, mut normal: Vector4D) -> Vector4D {
-        println!("normal_to_world: {:?}", normal);
         normal = self.get_transform().inverse().transpose().mul_vector4d(&normal);
         normal.w = 0.0;
         normal.normalize();
@@ -441,7 +295,7 @@ impl Intersectable for TestShape {
     }
     fn get_parent(&self) -> Option<Group> {
         if let Some(parent_id) = self.parent {
-            Some(get_group(parent_id))
+            Some(group_by_id(parent_id))
         } else {
             None
         }
@@ -495,14 +349,8 @@ impl Intersectable for Sphere {
         }
         let mut intersections : Vec<Intersection> = vec![];
         let sphere_clone = (*self).clone();
-        intersections.push(Intersection {
-            obj: Box::new(Shape::Sphere(sphere_clone.clone())),
-            t: (-b - discriminant.sqrt()) / (2.0 * a) 
-        });
-        intersections.push(Intersection {
-            obj: Box::new(Shape::Sphere(sphere_clone)),
-            t: (-b + discriminant.sqrt()) / (2.0 * a)
-        });
+        intersections.push(Intersection::new((-b - discriminant.sqrt()) / (2.0 * a), Shape::Sphere(sphere_clone.clone())));
+        intersections.push(Intersection::new((-b + discriminant.sqrt()) / (2.0 * a), Shape::Sphere(sphere_clone)));
         intersections
     }
 
@@ -539,9 +387,8 @@ impl Intersectable for Sphere {
         self.material = mat;
     }
     fn get_parent(&self) -> Option<Group> {
-        println!("get_parent Sphere: {:?}", self);
         if let Some(parent_id) = self.parent {
-            Some(get_group(parent_id))
+            Some(group_by_id(parent_id))
         } else {
             None
         }
@@ -597,9 +444,7 @@ impl Intersectable for Plane {
             vec![]
         } else {
             let t = -ray.origin.y / ray.direction.y;
-            vec![Intersection {
-                obj: Box::new(Shape::Plane((*self).clone())),
-                t: t }]
+            vec![Intersection::new(t, Shape::Plane((*self).clone()))]
         }
     }
     fn eq(&self, other: &Shape) -> bool {
@@ -628,7 +473,7 @@ impl Intersectable for Plane {
     }
     fn get_parent(&self) -> Option<Group> {
         if let Some(parent_id) = self.parent {
-            Some(get_group(parent_id))
+            Some(group_by_id(parent_id))
         } else {
             None
         }
@@ -676,14 +521,8 @@ impl Intersectable for Cube {
             vec![]
         } else {
             vec![
-                Intersection {
-                    t: tmin, 
-                    obj: Box::new(Shape::Cube((*self).clone()))
-                },
-                Intersection {
-                    t: tmax,
-                    obj: Box::new(Shape::Cube((*self).clone()))
-                }]
+                Intersection::new(tmin, Shape::Cube((*self).clone())),
+                Intersection::new(tmax, Shape::Cube((*self).clone()))]
         }
     }
     fn eq(&self, other: &Shape) -> bool {
@@ -719,7 +558,7 @@ impl Intersectable for Cube {
     }
     fn get_parent(&self) -> Option<Group> {
         if let Some(parent_id) = self.parent {
-            Some(get_group(parent_id))
+            Some(group_by_id(parent_id))
         } else {
             None
         }
@@ -787,18 +626,12 @@ impl Intersectable for Cylinder {
 
         let y0 = ray.origin().y + t0 * ray.dir().y;
         if self.minimum < y0 && y0 < self.maximum {
-            intersections.push(Intersection {
-                obj: Box::new(Shape::Cylinder(self.clone())),
-                t: t0
-            });
+            intersections.push(Intersection::new(t0, Shape::Cylinder(self.clone())));
         }
 
         let y1 = ray.origin().y + t1 * ray.dir().y;
         if self.minimum < y1 && y1 < self.maximum {
-            intersections.push(Intersection {
-                obj: Box::new(Shape::Cylinder(self.clone())),
-                t: t1
-            });
+            intersections.push(Intersection::new(t1, Shape::Cylinder(self.clone())));
         }
 
         self.intersect_caps(&ray, &mut intersections);
@@ -838,7 +671,7 @@ impl Intersectable for Cylinder {
     }
     fn get_parent(&self) -> Option<Group> {
         if let Some(parent_id) = self.parent {
-            Some(get_group(parent_id))
+            Some(group_by_id(parent_id))
         } else {
             None
         }
@@ -897,18 +730,12 @@ impl Cylinder {
 
         let mut t = (self.minimum - ray.origin().y) / ray.dir().y;
         if self.check_cap(ray, t) {
-            xs.push(Intersection {
-                obj: Box::new(Shape::Cylinder(self.clone())),
-                t: t
-            });
+            xs.push(Intersection::new(t, Shape::Cylinder(self.clone())));
         }
 
         t = (self.maximum - ray.origin().y) / ray.dir().y;
         if self.check_cap(ray, t) {
-            xs.push(Intersection {
-                obj: Box::new(Shape::Cylinder(self.clone())),
-                t: t
-            });
+            xs.push(Intersection::new(t, Shape::Cylinder(self.clone())));
         }
     }
 }
@@ -941,16 +768,7 @@ impl Intersectable for Cone {
             self.intersect_caps(&ray, &mut intersections);
             return intersections; 
         } else if a.abs() < 0.00000001 {
-            unsafe {
-                if let Some(true) = global_do_debug {
-                    println!(" bad shadow intersect point: {:?}", ray.origin() + (-c/(2.0 * b))*ray.dir());
-                    println!("a: {}, b:{}, c:{}", a, b, c);
-                }
-            }
-            intersections.push(Intersection {
-                obj: Box::new(Shape::Cone(self.clone())),
-                t: -c/(2.0 * b)
-            });
+            intersections.push(Intersection::new(-c/(2.0 * b), Shape::Cone(self.clone())));
             self.intersect_caps(&ray, &mut intersections);
             return intersections;
             //return vec![];
@@ -970,18 +788,12 @@ impl Intersectable for Cone {
 
         let y0 = t0.mul_add(ray.dir().y, ray.origin().y);
         if self.minimum < y0 && y0 < self.maximum {
-            intersections.push(Intersection {
-                obj: Box::new(Shape::Cone(self.clone())),
-                t: t0
-            });
+            intersections.push(Intersection::new(t0, Shape::Cone(self.clone())));
         }
 //        println!("int: {:?}", intersections);
         let y1 =  t1.mul_add(ray.dir().y,ray.origin().y);
         if self.minimum < y1 && y1 < self.maximum {
-            intersections.push(Intersection {
-                obj: Box::new(Shape::Cone(self.clone())),
-                t: t1
-            });
+            intersections.push(Intersection::new(t1, Shape::Cone(self.clone())));
         }
 
  //       println!("int: {:?}", intersections);
@@ -1007,11 +819,6 @@ impl Intersectable for Cone {
     }
     fn normal_at_local(&self, p: Vector4D) -> Vector4D {
         let dist = (p.x.powf(2.0) + p.z.powf(2.0)).sqrt();
-        unsafe {
-            if let Some(true) = global_do_debug {
-                println!("normal: p: {:?}, dist: {}, max: {} min: {}", p, dist, self.maximum, self.minimum);
-            }
-        }
         if dist < self.maximum.abs() && p.y >= (self.maximum - (utils::EPSILON)) {
             Vector4D::new_vector(0.0, 1.0, 0.0)
         } else if dist < self.minimum.abs() && p.y <= (self.minimum + (utils::EPSILON)) {
@@ -1032,7 +839,7 @@ impl Intersectable for Cone {
     }
     fn get_parent(&self) -> Option<Group> {
         if let Some(parent_id) = self.parent {
-            Some(get_group(parent_id))
+            Some(group_by_id(parent_id))
         } else {
             None
         }
@@ -1091,18 +898,12 @@ impl Cone {
 
         let mut t = (self.minimum - ray.origin().y) / ray.dir().y;
         if self.check_cap(ray, t, self.minimum) {
-            xs.push(Intersection {
-                obj: Box::new(Shape::Cone(self.clone())),
-                t: t
-            });
+            xs.push(Intersection::new(t, Shape::Cone(self.clone())));
         }
 
         t = (self.maximum - ray.origin().y) / ray.dir().y;
         if self.check_cap(ray, t, self.maximum) {
-            xs.push(Intersection {
-                obj: Box::new(Shape::Cone(self.clone())),
-                t: t
-            });
+            xs.push(Intersection::new(t, Shape::Cone(self.clone())));
         }
     }
 }
@@ -1171,9 +972,8 @@ impl Intersectable for Group {
         put_group(self.clone());
     }
     fn get_parent(&self) -> Option<Group> {
-        println!("Group::get_parent: self: {:?}", self);
         if let Some(parent_id) = self.parent {
-            Some(get_group(parent_id))
+            Some(group_by_id(parent_id))
         } else {
             None
         }
@@ -1182,8 +982,12 @@ impl Intersectable for Group {
         self.parent = Some(parent.id);
     }
 
+    // Around the children's bounds in the group's space, empty for an empty group
     fn get_bounds(&self) -> Bounds {
-        todo!()
+        self.children.iter().fold(Bounds::empty(), |bounds, child| {
+            let child = child.as_intersectable();
+            bounds.union(&child.get_bounds().transform(&child.get_transform()))
+        })
     }
 }
 
@@ -1252,8 +1056,9 @@ use std::sync::Mutex;
 lazy_static! {
     static ref GROUP_REPO: Mutex<HashMap<usize, Group>> = Mutex::new(HashMap::new());
 }
-fn get_group(id: usize) -> Group {
-    println!("get_group: {}", id);
+// Looks up the current state of a group, custom shapes keep the parent id
+// and use this so they see transforms set on the group after add_child
+pub fn group_by_id(id: usize) -> Group {
     let grp_repo = GROUP_REPO.lock().unwrap();
     grp_repo.get(&id).unwrap().clone()
 }
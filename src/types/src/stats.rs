@@ -0,0 +1,101 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Counts of the work done tracing rays, to find out why a scene is slow
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RayStats {
+    // World::intersect calls, one for every ray traced through the world
+    pub world_intersections: usize,
+    // Shape::intersect calls by shape type, shapes inside groups are counted too
+    pub shape_tests: BTreeMap<&'static str, usize>,
+    pub shadow_rays: usize,
+    // Times reflected_color and refracted_color recursed into another ray
+    pub reflection_rays: usize,
+    pub refraction_rays: usize,
+}
+
+impl RayStats {
+    pub fn total_shape_tests(&self) -> usize {
+        self.shape_tests.values().sum()
+    }
+
+    pub fn add(&mut self, other: &RayStats) {
+        self.world_intersections += other.world_intersections;
+        for (shape, count) in other.shape_tests.iter() {
+            *self.shape_tests.entry(shape).or_insert(0) += count;
+        }
+        self.shadow_rays += other.shadow_rays;
+        self.reflection_rays += other.reflection_rays;
+        self.refraction_rays += other.refraction_rays;
+    }
+}
+
+impl fmt::Display for RayStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "world intersections: {}", self.world_intersections)?;
+        writeln!(f, "shadow rays: {}", self.shadow_rays)?;
+        writeln!(f, "reflection rays: {}", self.reflection_rays)?;
+        writeln!(f, "refraction rays: {}", self.refraction_rays)?;
+        writeln!(f, "shape intersection tests: {}", self.total_shape_tests())?;
+        for (shape, count) in self.shape_tests.iter() {
+            writeln!(f, "    {}: {}", shape, count)?;
+        }
+        Ok(())
+    }
+}
+
+// The counters are kept per thread so they don't have to be passed through the renderer, and
+// only while counting is turned on. COUNTING mirrors whether STATS is set, the hooks check it
+// first so they cost a flag test when counting is off.
+thread_local! {
+    static STATS: RefCell<Option<RayStats>> = const { RefCell::new(None) };
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    // Rays are always counted, it's a lot cheaper than the counters above
+    static RAYS: Cell<usize> = const { Cell::new(0) };
+}
+
+// Rays traced through the world on this thread so far, the difference between two calls is the
+// number traced in between
+pub fn rays_traced() -> usize {
+    RAYS.with(|rays| rays.get())
+}
+
+pub(crate) fn count_ray() {
+    RAYS.with(|rays| rays.set(rays.get().wrapping_add(1)));
+}
+
+// Starts counting on this thread from zero
+pub fn enable_stats() {
+    STATS.with(|stats| *stats.borrow_mut() = Some(Default::default()));
+    COUNTING.with(|counting| counting.set(true));
+}
+
+// Stops counting and returns the counts
+pub fn disable_stats() -> RayStats {
+    COUNTING.with(|counting| counting.set(false));
+    STATS.with(|stats| stats.borrow_mut().take().unwrap_or_default())
+}
+
+// The counts so far, which start again from zero. Counting has to be on.
+pub fn take_stats() -> RayStats {
+    STATS.with(|stats| stats.borrow_mut().as_mut().map(std::mem::take).unwrap_or_default())
+}
+
+pub fn stats_enabled() -> bool {
+    COUNTING.with(|counting| counting.get())
+}
+
+// Runs count on the counters when counting is on
+#[inline]
+pub fn record_stats<F: FnOnce(&mut RayStats)>(count: F) {
+    if !stats_enabled() {
+        return;
+    }
+    STATS.with(|stats| {
+        if let Some(ref mut stats) = *stats.borrow_mut() {
+            count(stats);
+        }
+    });
+}
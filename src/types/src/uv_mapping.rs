@@ -0,0 +1,300 @@
+use crate::Color;
+use crate::Matrix4x4;
+use crate::Vector4D;
+use crate::Patternable;
+use std::f64::consts::PI;
+use std::fmt::Debug;
+use utils::EPSILON;
+
+// UV patterns are 2D patterns looked up with u, v in the range 0.0 to 1.0. They're put on a
+// surface with a TextureMapPattern which turns the 3D point into u, v with one of the mappings.
+pub trait UvPatternable: UvPatternClone + Debug {
+    fn uv_pattern_at(&self, u: f64, v: f64) -> Color;
+}
+
+pub type BoxedUvPattern = Box<dyn UvPatternable + Send + Sync>;
+
+pub trait UvPatternClone {
+    fn clone_uv_pattern(&self) -> BoxedUvPattern;
+}
+
+impl<T> UvPatternClone for T where T: 'static + UvPatternable + Clone + Send + Sync {
+    fn clone_uv_pattern(&self) -> BoxedUvPattern {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for BoxedUvPattern {
+    fn clone(&self) -> Self {
+        self.clone_uv_pattern()
+    }
+}
+
+// A checker board with width checks along u and height checks along v
+#[derive(Debug, Clone, Copy)]
+pub struct UvCheckersPattern {
+    pub width: f64,
+    pub height: f64,
+    pub colors: [Color; 2],
+}
+
+impl UvCheckersPattern {
+    pub fn new(width: f64, height: f64, color_1: Color, color_2: Color) -> UvCheckersPattern {
+        UvCheckersPattern {
+            width,
+            height,
+            colors: [color_1, color_2],
+        }
+    }
+}
+
+impl UvPatternable for UvCheckersPattern {
+    fn uv_pattern_at(&self, u: f64, v: f64) -> Color {
+        let u2 = (u * self.width).floor();
+        let v2 = (v * self.height).floor();
+        if (u2 + v2) as i64 % 2 == 0 {
+            self.colors[0]
+        } else {
+            self.colors[1]
+        }
+    }
+}
+
+// A test pattern with a different color in each corner, useful to check that a mapping is
+// oriented correctly.
+#[derive(Debug, Clone, Copy)]
+pub struct UvAlignCheckPattern {
+    pub main: Color,
+    pub upper_left: Color,
+    pub upper_right: Color,
+    pub bottom_left: Color,
+    pub bottom_right: Color,
+}
+
+impl UvAlignCheckPattern {
+    pub fn new(main: Color, upper_left: Color, upper_right: Color, bottom_left: Color, bottom_right: Color) -> UvAlignCheckPattern {
+        UvAlignCheckPattern {
+            main,
+            upper_left,
+            upper_right,
+            bottom_left,
+            bottom_right,
+        }
+    }
+}
+
+impl UvPatternable for UvAlignCheckPattern {
+    fn uv_pattern_at(&self, u: f64, v: f64) -> Color {
+        if v > 0.8 {
+            if u < 0.2 {
+                return self.upper_left;
+            }
+            if u > 0.8 {
+                return self.upper_right;
+            }
+        } else if v < 0.2 {
+            if u < 0.2 {
+                return self.bottom_left;
+            }
+            if u > 0.8 {
+                return self.bottom_right;
+            }
+        }
+        self.main
+    }
+}
+
+// Map a point on a unit sphere, u goes around the equator and v from the south to north pole.
+pub fn spherical_map(p: Vector4D) -> (f64, f64) {
+    let theta = p.x.atan2(p.z);
+    let radius = Vector4D::new_vector(p.x, p.y, p.z).norm();
+    // the center of the sphere has no direction, treat it like a point on the equator
+    let phi = if radius > 0.0 { (p.y / radius).clamp(-1.0, 1.0).acos() } else { PI / 2.0 };
+    let raw_u = theta / (2.0 * PI);
+    let u = 1.0 - (raw_u + 0.5);
+    let v = 1.0 - phi / PI;
+    (u, v)
+}
+
+// Map a point on the xz plane, the texture repeats every unit.
+pub fn planar_map(p: Vector4D) -> (f64, f64) {
+    (p.x.rem_euclid(1.0), p.z.rem_euclid(1.0))
+}
+
+// Map a point on a unit cylinder, u goes around the y-axis and v repeats every unit along y.
+pub fn cylindrical_map(p: Vector4D) -> (f64, f64) {
+    let theta = p.x.atan2(p.z);
+    let raw_u = theta / (2.0 * PI);
+    let u = 1.0 - (raw_u + 0.5);
+    let v = p.y.rem_euclid(1.0);
+    (u, v)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CubeFace {
+    Left = 0,
+    Front = 1,
+    Right = 2,
+    Back = 3,
+    Up = 4,
+    Down = 5,
+}
+
+// Which face of the unit cube the point is on, it's the axis with the largest component
+pub fn cube_face_from_point(p: Vector4D) -> CubeFace {
+    let coord = p.x.abs().max(p.y.abs()).max(p.z.abs());
+    if coord == p.x {
+        CubeFace::Right
+    } else if coord == -p.x {
+        CubeFace::Left
+    } else if coord == p.y {
+        CubeFace::Up
+    } else if coord == -p.y {
+        CubeFace::Down
+    } else if coord == p.z {
+        CubeFace::Front
+    } else {
+        CubeFace::Back
+    }
+}
+
+// u, v on a single face of the cube, each face is laid out as if looking at it from outside the
+// cube.
+pub fn cube_face_uv(face: CubeFace, p: Vector4D) -> (f64, f64) {
+    match face {
+        CubeFace::Front => (((p.x + 1.0).rem_euclid(2.0)) / 2.0, ((p.y + 1.0).rem_euclid(2.0)) / 2.0),
+        CubeFace::Back => (((1.0 - p.x).rem_euclid(2.0)) / 2.0, ((p.y + 1.0).rem_euclid(2.0)) / 2.0),
+        CubeFace::Left => (((p.z + 1.0).rem_euclid(2.0)) / 2.0, ((p.y + 1.0).rem_euclid(2.0)) / 2.0),
+        CubeFace::Right => (((1.0 - p.z).rem_euclid(2.0)) / 2.0, ((p.y + 1.0).rem_euclid(2.0)) / 2.0),
+        CubeFace::Up => (((p.x + 1.0).rem_euclid(2.0)) / 2.0, ((1.0 - p.z).rem_euclid(2.0)) / 2.0),
+        CubeFace::Down => (((p.x + 1.0).rem_euclid(2.0)) / 2.0, ((p.z + 1.0).rem_euclid(2.0)) / 2.0),
+    }
+}
+
+pub fn cube_map(p: Vector4D) -> (CubeFace, f64, f64) {
+    let face = cube_face_from_point(p);
+    let (u, v) = cube_face_uv(face, p);
+    (face, u, v)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UvMapping {
+    Spherical,
+    Planar,
+    Cylindrical,
+    // Every face of the cube gets the same texture, use CubeMapPattern for a texture per face.
+    Cube,
+}
+
+impl UvMapping {
+    pub fn map(&self, p: Vector4D) -> (f64, f64) {
+        match *self {
+            UvMapping::Spherical => spherical_map(p),
+            UvMapping::Planar => planar_map(p),
+            UvMapping::Cylindrical => cylindrical_map(p),
+            UvMapping::Cube => {
+                let (_, u, v) = cube_map(p);
+                (u, v)
+            },
+        }
+    }
+
+    // The object space directions that u and v increase in at the point, used to orient tangent
+    // space normal maps.
+    pub fn tangent_frame(&self, p: Vector4D) -> (Vector4D, Vector4D) {
+        match *self {
+            UvMapping::Planar => (Vector4D::new_vector(1.0, 0.0, 0.0), Vector4D::new_vector(0.0, 0.0, 1.0)),
+            UvMapping::Spherical | UvMapping::Cylindrical => {
+                // u decreases going around from +z towards +x, so the tangent points the other way
+                let around = Vector4D::new_vector(-p.z, 0.0, p.x);
+                let tangent = if around.norm() < EPSILON {
+                    // at the poles any direction will do
+                    Vector4D::new_vector(1.0, 0.0, 0.0)
+                } else {
+                    around.normalized()
+                };
+                if *self == UvMapping::Cylindrical {
+                    (tangent, Vector4D::new_vector(0.0, 1.0, 0.0))
+                } else {
+                    let normal = Vector4D::new_vector(p.x, p.y, p.z).normalized();
+                    (tangent, tangent.cross(normal))
+                }
+            },
+            UvMapping::Cube => {
+                match cube_face_from_point(p) {
+                    CubeFace::Front => (Vector4D::new_vector(1.0, 0.0, 0.0), Vector4D::new_vector(0.0, 1.0, 0.0)),
+                    CubeFace::Back => (Vector4D::new_vector(-1.0, 0.0, 0.0), Vector4D::new_vector(0.0, 1.0, 0.0)),
+                    CubeFace::Left => (Vector4D::new_vector(0.0, 0.0, 1.0), Vector4D::new_vector(0.0, 1.0, 0.0)),
+                    CubeFace::Right => (Vector4D::new_vector(0.0, 0.0, -1.0), Vector4D::new_vector(0.0, 1.0, 0.0)),
+                    CubeFace::Up => (Vector4D::new_vector(1.0, 0.0, 0.0), Vector4D::new_vector(0.0, 0.0, -1.0)),
+                    CubeFace::Down => (Vector4D::new_vector(1.0, 0.0, 0.0), Vector4D::new_vector(0.0, 0.0, 1.0)),
+                }
+            },
+        }
+    }
+}
+
+// A texture map pattern maps the pattern space point to u, v and looks up the uv pattern
+#[derive(Debug, Clone)]
+pub struct TextureMapPattern {
+    pub uv_pattern: BoxedUvPattern,
+    pub mapping: UvMapping,
+    pub transform: Matrix4x4,
+}
+
+impl TextureMapPattern {
+    pub fn new<P: 'static + UvPatternable + Clone + Send + Sync>(uv_pattern: P, mapping: UvMapping) -> TextureMapPattern {
+        TextureMapPattern {
+            uv_pattern: Box::new(uv_pattern),
+            mapping,
+            transform: Matrix4x4::new(),
+        }
+    }
+}
+
+impl Patternable for TextureMapPattern {
+    fn get_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4x4) {
+        self.transform = transform;
+    }
+
+    fn pattern_at(&self, point: Vector4D) -> Color {
+        let (u, v) = self.mapping.map(point);
+        self.uv_pattern.uv_pattern_at(u, v)
+    }
+}
+
+// A cube map has a separate uv pattern for each face of the cube, indexed by CubeFace.
+#[derive(Debug, Clone)]
+pub struct CubeMapPattern {
+    pub faces: [BoxedUvPattern; 6],
+    pub transform: Matrix4x4,
+}
+
+impl CubeMapPattern {
+    pub fn new(left: BoxedUvPattern, front: BoxedUvPattern, right: BoxedUvPattern,
+               back: BoxedUvPattern, up: BoxedUvPattern, down: BoxedUvPattern) -> CubeMapPattern {
+        CubeMapPattern {
+            faces: [left, front, right, back, up, down],
+            transform: Matrix4x4::new(),
+        }
+    }
+}
+
+impl Patternable for CubeMapPattern {
+    fn get_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4x4) {
+        self.transform = transform;
+    }
+
+    fn pattern_at(&self, point: Vector4D) -> Color {
+        let (face, u, v) = cube_map(point);
+        self.faces[face as usize].uv_pattern_at(u, v)
+    }
+}
@@ -10,55 +10,75 @@ use crate::Color;
 use crate::Ray;
 use crate::Vector4D;
 use crate::hit;
+use crate::hit_object;
 use crate::positive_hits;
-use crate::color_at;
+use crate::color_and_hit_at;
 use crate::ShadeComputation;
 use utils::*;
-use crate::global_do_debug;
+use crate::Background;
+use crate::PhotonMap;
+use crate::Medium;
+use crate::Volume;
+use crate::Bounds;
+use crate::AmbientOcclusion;
+use crate::Sampler;
+use crate::sample_cone;
+use crate::refractive_indices;
+use crate::dispersions;
+use crate::record_stats;
+use crate::count_ray;
+use crate::record_shadow_test;
+use crate::record_next_ray_kind;
+use crate::RayKind;
+use std::f64::consts::PI;
 
 pub struct World {
     pub light_source: LightSource,
     pub objects: Vec<Shape>,
+    // Color seen by rays that miss every object
+    pub background: Background,
+    // How many shadow rays are sent towards each emissive object to light the surfaces around
+    // it, 0 turns off lighting from emissive objects.
+    pub area_light_samples: usize,
+    // Caustics from light focused by glass and mirrors, see build_photon_map
+    pub photon_map: Option<PhotonMap>,
+    // Fog filling the whole world
+    pub fog: Option<Medium>,
+    // Smoke and other media inside shapes
+    pub volumes: Vec<Volume>,
+    // Scales the ambient term of materials by how open the surface is
+    pub ambient_occlusion: Option<AmbientOcclusion>,
 }
 
 impl World {
     pub fn new() -> World {
         World {
             light_source: LightSource::new(Color::new(1.0, 1.0, 1.0), Vector4D::new_point(-10.0, 10.0, -10.0)),
-            objects: vec![]
+            objects: vec![],
+            background: Default::default(),
+            area_light_samples: 0,
+            photon_map: None,
+            fog: None,
+            volumes: vec![],
+            ambient_occlusion: None,
         }
     }
 
     pub fn intersect(&self, ray: &Ray) -> Intersections {
-        let mut vs : Intersections = vec![];
-        for o in self.objects.iter() {
-            match o {
-                Shape::Sphere(s) => {
-                    vs.extend(ray.intersect(s));
-                },
-                Shape::TestShape(t) => {
-                    vs.extend(ray.intersect(t));
-                },
-                Shape::Plane(p) => {
-                    vs.extend(ray.intersect(p));
-                },
-                Shape::Cube(c) => {
-                    vs.extend(ray.intersect(c));
-                },
-                Shape::Cylinder(c) => {
-                    vs.extend(ray.intersect(c));
-                },
-                Shape::Cone(c) => {
-                    vs.extend(ray.intersect(c));
-                },
-                Shape::Group(g) => {
-                    vs.extend(ray.intersect(g))
-                }
-            }
+        self.intersect_objects(ray).into_iter().map(|(_, x)| x).collect()
+    }
 
+    // Like intersect, each intersection comes with the index in objects of the object it's on,
+    // for a shape in a group that's the group
+    pub fn intersect_objects(&self, ray: &Ray) -> Vec<(usize, Intersection)> {
+        count_ray();
+        record_stats(|stats| stats.world_intersections += 1);
+        let mut vs: Vec<(usize, Intersection)> = vec![];
+        for (i, o) in self.objects.iter().enumerate() {
+            vs.extend(o.intersect(ray).into_iter().map(|x| (i, x)));
         }
-        vs.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
-        vs.dedup_by(|a, b| f64_eq(a.t, b.t));
+        vs.sort_by(|(_, a), (_, b)| a.t.partial_cmp(&b.t).unwrap());
+        vs.dedup_by(|(_, a), (_, b)| f64_eq(a.t, b.t));
         // NOTE: Lets not filter here, we can use the hit() function to locate the first non
         // negative hit.  
          //vs = vs.iter().filter(|a| a.t >= 0.0).cloned().collect::<Vec<Intersection>>();
@@ -70,6 +90,7 @@ impl World {
         let distance_to_light = to_light_vec.norm();
         to_light_vec.normalize(); 
         let ray_to_light = Ray::new(point, to_light_vec);
+        record_stats(|stats| stats.shadow_rays += 1);
         let xs = ray_to_light.intersect_world(self);
         let xs = positive_hits(&xs);
         for a_xs in xs.iter() {
@@ -87,17 +108,12 @@ impl World {
         let distance_to_light = to_light_vec.norm();
         to_light_vec.normalize(); 
         let ray_to_light = Ray::new(point, to_light_vec);
+        record_stats(|stats| stats.shadow_rays += 1);
         let xs = ray_to_light.intersect_world(self);
-        if let Some(a_xs) = hit(&xs) {
-            unsafe {
-            if let Some(true) = global_do_debug {
-                println!("bad shadow: xs:{:?}, ray_to_light: {:?}", xs, ray_to_light);
-            }
-            }
-            a_xs.t < distance_to_light 
-        } else  {
-            false
-        }
+        let blocker_t = hit(&xs).map(|a_xs| a_xs.t);
+        let shadowed = blocker_t.map_or(false, |t| t < distance_to_light);
+        record_shadow_test(point, distance_to_light, blocker_t, shadowed);
+        shadowed
     }
 
     pub fn is_shadowed_disabled(&self, point: Vector4D) -> bool {
@@ -112,15 +128,18 @@ impl World {
         if shade_computation.obj.get_material().reflective == 0.0 {
             return Color::BLACK;
         }
-        let reflect_ray = Ray::new(shade_computation.over_point, shade_computation.reflectv);
-        let color = color_at(self, reflect_ray, reflect_rays_remaining - 1);
-        return color * shade_computation.obj.get_material().reflective;
+        let material = shade_computation.obj.get_material();
+        record_stats(|stats| stats.reflection_rays += 1);
+        record_next_ray_kind(RayKind::Reflection);
+        let ray = Ray { wavelength: shade_computation.wavelength,
+                        ..Ray::new(shade_computation.over_point, shade_computation.reflectv) };
+        let color = self.glossy_ray_color(ray, shade_computation.normalv, material.reflect_roughness,
+                                          material.glossy_samples, reflect_rays_remaining - 1,
+                                          shade_computation.n1_medium.as_deref());
+        return color * material.reflective;
     }
 
     pub fn refracted_color(&self, shade_computation: &ShadeComputation, reflectrays_remaining: usize) -> Color {
-        // Compute snell's law 
-        // sin(theta_i) / sin(theta_t) = n1/n2 
-        
         //println!("refracted color eyev: {:?}\npoint: {:?}\nunder_point: {:?}\nn1: {:?},\nn2: {:?},\nobj: {:?}",
         //         shade_computation.eyev,
         //         shade_computation.point,
@@ -128,24 +147,189 @@ impl World {
         //         shade_computation.n1,
         //         shade_computation.n2,
         //         *shade_computation.obj);
-        let n1_n2_ratio = shade_computation.n1 / shade_computation.n2;
-        let cos_theta_i  = shade_computation.eyev.dot( shade_computation.normalv);
-        let sin2_theta_t = n1_n2_ratio.powi(2) * (1.0 - cos_theta_i.powi(2));
+        let material = shade_computation.obj.get_material();
+        if reflectrays_remaining == 0 || material.transparency == 0.0 {
+            return Color::BLACK;
+        }
+
+        let dispersive = [&shade_computation.n1_medium, &shade_computation.n2_medium].iter()
+            .any(|medium| medium.as_ref().map_or(false, |m| m.get_material().dispersion.is_some()));
+        if !dispersive {
+            return match refraction_direction(shade_computation, shade_computation.n1, shade_computation.n2) {
+                Some(direction) => self.refracted_ray_color(shade_computation, direction,
+                                                            shade_computation.wavelength, reflectrays_remaining),
+                // total internal reflection
+                None => Color::BLACK,
+            } * material.transparency;
+        }
 
+        let index_at = |medium: &Option<Box<Shape>>, wavelength: f64| match medium {
+            Some(m) => m.get_material().refractive_index_at(wavelength),
+            None => refractive_indices::VACUUM,
+        };
+        let wavelength_color = |wavelength: f64| {
+            let n1 = index_at(&shade_computation.n1_medium, wavelength);
+            let n2 = index_at(&shade_computation.n2_medium, wavelength);
+            refraction_direction(shade_computation, n1, n2)
+                .map_or(Color::BLACK, |direction| {
+                    self.refracted_ray_color(shade_computation, direction, Some(wavelength), reflectrays_remaining)
+                })
+        };
+        // The light was split up at an earlier surface, only its color is left to follow
+        if let Some(wavelength) = shade_computation.wavelength {
+            return wavelength_color(wavelength) * material.transparency;
+        }
+        // Each color bends by a different amount, trace a ray per channel and keep that channel
+        let red = wavelength_color(dispersions::RED_WAVELENGTH).red;
+        let green = wavelength_color(dispersions::GREEN_WAVELENGTH).green;
+        let blue = wavelength_color(dispersions::BLUE_WAVELENGTH).blue;
+        Color::new(red, green, blue) * material.transparency
+    }
+
+    fn refracted_ray_color(&self, shade_computation: &ShadeComputation, direction: Vector4D,
+                           wavelength: Option<f64>, reflectrays_remaining: usize) -> Color {
+        let material = shade_computation.obj.get_material();
+        record_stats(|stats| stats.refraction_rays += 1);
+        record_next_ray_kind(RayKind::Refraction);
+        let ray = Ray { wavelength, ..Ray::new(shade_computation.under_point, direction) };
+        self.glossy_ray_color(ray, -shade_computation.normalv, material.refract_roughness,
+                              material.glossy_samples, reflectrays_remaining - 1,
+                              shade_computation.n2_medium.as_deref())
+    }
 
-        if reflectrays_remaining == 0 || sin2_theta_t > 1.0 || shade_computation.obj.get_material().transparency == 0.0 {
+    // Shoots photon_count photons from the light source to find the caustics, it has to be done
+    // again when the objects or light move.
+    pub fn build_photon_map(&mut self, photon_count: usize) {
+        self.photon_map = Some(PhotonMap::trace(self, photon_count, 8));
+    }
+
+    // Diffuse light from caustics on the surface
+    pub fn caustics(&self, shade_computation: &ShadeComputation) -> Color {
+        let photon_map = match self.photon_map {
+            Some(ref photon_map) => photon_map,
+            None => return Color::BLACK,
+        };
+        let material = shade_computation.obj.get_material();
+        if material.diffuse == 0.0 || material.transparency > 0.0 {
             return Color::BLACK;
         }
+        let albedo = material.color_at_object(shade_computation.obj.as_intersectable(), shade_computation.point) *
+            material.diffuse;
+        albedo * photon_map.irradiance_at(shade_computation.point, shade_computation.normalv) * (1.0 / PI)
+    }
 
-        let cos_t = (1.0 - sin2_theta_t).sqrt();
-         
-        let refracted_ray_dir = (n1_n2_ratio * cos_theta_i - cos_t) * shade_computation.normalv - 
-            n1_n2_ratio * shade_computation.eyev;
-        let refracted_ray = Ray::new(shade_computation.under_point, refracted_ray_dir);
-        let refracted_color_at = color_at(self, refracted_ray, reflectrays_remaining - 1);
-        let refracted_color =  refracted_color_at * 
-            shade_computation.obj.get_material().transparency;
-        refracted_color 
+    // Diffuse light reaching the surface from emissive objects. Each emitter is sampled with rays
+    // in the cone around its bounding sphere, a ray that hits the emitter first brings back its
+    // emissive color. Emitters with infinite bounds like planes can't be sampled.
+    pub fn area_light(&self, shade_computation: &ShadeComputation) -> Color {
+        if self.area_light_samples == 0 {
+            return Color::BLACK;
+        }
+        let material = shade_computation.obj.get_material();
+        if material.diffuse == 0.0 {
+            return Color::BLACK;
+        }
+        let object = shade_computation.obj.as_intersectable();
+        let albedo = material.color_at_object(object, shade_computation.over_point) * material.diffuse;
+        let origin = shade_computation.over_point;
+        let normalv = shade_computation.normalv;
+        let mut sampler = Sampler::from_vectors(origin, shade_computation.eyev);
+        let mut total = Color::BLACK;
+        for (index, object) in self.objects.iter().enumerate() {
+            let mut emitters = vec![];
+            find_emitters(object, Matrix4x4::new(), &mut emitters);
+            for (emitter, to_world) in emitters {
+                // computations made up without World::intersect don't know the object's index
+                let in_shaded_object = shade_computation.object.map_or(true, |i| i == index);
+                if in_shaded_object && emitter.eq(&shade_computation.obj) {
+                    continue;
+                }
+                let sphere = match sphere_around(emitter.as_intersectable().get_bounds(), to_world) {
+                    Some(sphere) => sphere,
+                    None => continue,
+                };
+                let weight = self.emitter_weight(index, emitter, sphere, origin, normalv, &mut sampler);
+                total = total + emitter.get_material().emissive * weight;
+            }
+        }
+        total * albedo
+    }
+
+    // Estimate of the cos weighted solid angle emitter covers seen from origin, divided by PI.
+    // Only rays that reach the emitter itself, in the object at index, count.
+    fn emitter_weight(&self, index: usize, emitter: &Shape, (center, radius): (Vector4D, f64),
+                      origin: Vector4D, normalv: Vector4D, sampler: &mut Sampler) -> f64 {
+        let to_center = center - origin;
+        let distance = to_center.norm();
+        // Inside the bounding sphere the emitter can be in any direction, fall back to the
+        // hemisphere. The weight is the solid angle sampled times cos/PI for diffuse.
+        let (half_angle, solid_angle) = if distance <= radius {
+            (PI / 2.0, 2.0 * PI)
+        } else {
+            let sin_max = radius / distance;
+            let cos_max = (1.0 - sin_max * sin_max).max(0.0).sqrt();
+            (sin_max.asin(), 2.0 * PI * (1.0 - cos_max))
+        };
+        let axis = if distance <= radius { normalv } else { to_center };
+        let mut sum = 0.0;
+        for _ in 0..self.area_light_samples {
+            let dir = sample_cone(axis, half_angle, sampler.next_f64(), sampler.next_f64());
+            let cos = dir.dot(normalv);
+            if cos <= 0.0 {
+                continue;
+            }
+            if let Some((object, h)) = hit_object(&self.intersect_objects(&Ray::new(origin, dir))) {
+                if object == index && h.obj.eq(emitter) {
+                    sum += cos;
+                }
+            }
+        }
+        sum / self.area_light_samples as f64 * solid_angle / PI
+    }
+
+    // Average color seen by rays spread in a cone around direction, the rays stay on the side
+    // of the surface that side points to. With no roughness it's the single ray along direction.
+    // medium is the object the rays travel through, if any.
+    pub fn glossy_color(&self, origin: Vector4D, direction: Vector4D, side: Vector4D,
+                        roughness: f64, samples: usize, remaining: usize, medium: Option<&Shape>) -> Color {
+        self.glossy_ray_color(Ray::new(origin, direction), side, roughness, samples, remaining, medium)
+    }
+
+    // glossy_color around the direction of ray, the spread rays keep its wavelength
+    fn glossy_ray_color(&self, ray: Ray, side: Vector4D, roughness: f64, samples: usize,
+                        remaining: usize, medium: Option<&Shape>) -> Color {
+        if roughness <= 0.0 || samples == 0 {
+            return self.color_through(ray, remaining, medium);
+        }
+        let half_angle = roughness.min(1.0) * PI / 2.0;
+        let mut sampler = Sampler::from_vectors(ray.origin, ray.direction);
+        let mut total = Color::BLACK;
+        for _ in 0..samples {
+            let mut dir = sample_cone(ray.direction, half_angle, sampler.next_f64(), sampler.next_f64());
+            let d = dir.dot(side);
+            if d < 0.0 {
+                // went through the surface, mirror it back
+                dir = dir - (2.0 * d) * side;
+            }
+            total = total + self.color_through(Ray { direction: dir, ..ray }, remaining, medium);
+        }
+        total * (1.0 / samples as f64)
+    }
+
+    // Color seen along a ray travelling through the inside of medium, attenuated by how much of
+    // the light the medium absorbs before the ray hits something.
+    pub fn color_through(&self, ray: Ray, remaining: usize, medium: Option<&Shape>) -> Color {
+        let (color, hit) = color_and_hit_at(self, ray, remaining);
+        match medium {
+            Some(medium) if medium.get_material().is_absorbing() => {
+                let distance = match hit {
+                    Some(sc) => sc.t * ray.dir().norm(),
+                    None => std::f64::INFINITY,
+                };
+                color * medium.get_material().transmittance(distance)
+            },
+            _ => color,
+        }
     }
 }
 
@@ -166,3 +350,57 @@ impl Default for World {
         w
     }
 }
+
+// Direction of the ray refracted from a medium with index n1 into one with index n2 by snell's
+// law, None for total internal reflection.
+pub fn refraction_direction(shade_computation: &ShadeComputation, n1: f64, n2: f64) -> Option<Vector4D> {
+    // sin(theta_i) / sin(theta_t) = n1/n2
+    let n1_n2_ratio = n1 / n2;
+    let cos_theta_i = shade_computation.eyev.dot(shade_computation.normalv);
+    let sin2_theta_t = n1_n2_ratio.powi(2) * (1.0 - cos_theta_i.powi(2));
+    if sin2_theta_t > 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_theta_t).sqrt();
+    Some((n1_n2_ratio * cos_theta_i - cos_t) * shade_computation.normalv -
+         n1_n2_ratio * shade_computation.eyev)
+}
+
+// A sphere in world space around the object's bounds, None if the bounds are infinite or empty
+pub fn bounding_sphere(shape: &Shape) -> Option<(Vector4D, f64)> {
+    let object = shape.as_intersectable();
+    sphere_around(object.get_bounds(), object.get_transform())
+}
+
+// Sphere around bounds moved by transform, None for infinite or empty bounds
+fn sphere_around(bounds: Bounds, transform: Matrix4x4) -> Option<(Vector4D, f64)> {
+    if bounds.is_infinite() || bounds.is_empty() {
+        return None;
+    }
+    let corners: Vec<Vector4D> = bounds.corners().iter().map(|c| transform.mul_vector4d(c)).collect();
+    let sum = corners.iter().fold(Vector4D::new_vector(0.0, 0.0, 0.0), |acc, c| {
+        acc + Vector4D::new_vector(c.x, c.y, c.z)
+    });
+    let center = Vector4D::new_point(sum.x / 8.0, sum.y / 8.0, sum.z / 8.0);
+    let radius = corners.iter().map(|c| (*c - center).norm()).fold(0.0, f64::max);
+    Some((center, radius))
+}
+
+// The emissive shapes in shape with the transform from their object space to world space.
+// A group is shaded with the material of the child that was hit, so it glows through its
+// emissive children and not its own material.
+fn find_emitters<'a>(shape: &'a Shape, parent_transform: Matrix4x4, emitters: &mut Vec<(&'a Shape, Matrix4x4)>) {
+    let transform = parent_transform.mul(&shape.as_intersectable().get_transform());
+    match *shape {
+        Shape::Group(ref group) => {
+            for child in group.children.iter() {
+                find_emitters(child, transform, emitters);
+            }
+        },
+        _ => {
+            if shape.get_material().is_emissive() {
+                emitters.push((shape, transform));
+            }
+        },
+    }
+}
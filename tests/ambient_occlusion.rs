@@ -0,0 +1,80 @@
+use utils::*;
+use types::*;
+use std::f64::consts::PI;
+extern crate raytracer_challenge_rs;
+use raytracer_challenge_rs::{RenderConfig, Integrator, render_pixel};
+
+// A floor with a ceiling two units above it, the light is above the ceiling so the floor only
+// gets the ambient term
+fn floor_and_ceiling() -> World {
+    let mut w = World::new();
+    w.light_source = LightSource::new(Color::WHITE, Vector4D::new_point(0.0, 5.0, 0.0));
+    w.objects.push(Shape::Plane(Plane::new()));
+    let mut ceiling = Plane::new();
+    ceiling.set_transform(Matrix4x4::translation(0.0, 2.0, 0.0));
+    w.objects.push(Shape::Plane(ceiling));
+    w
+}
+
+#[test]
+fn ambient_occlusion_open_surface() {
+    let w = floor_and_ceiling();
+    let p = Vector4D::new_point(0.0, 0.0001, 0.0);
+    let up = Vector4D::new_vector(0.0, 1.0, 0.0);
+    assert_f64_eq!(AmbientOcclusion::new(32, 1.0).visibility(&w, p, up), 1.0);
+    // the ceiling is in the way of every ray once it's close enough to count
+    assert_f64_eq!(AmbientOcclusion::new(32, 1000.0).visibility(&w, p, up), 0.0);
+    assert_f64_eq!(AmbientOcclusion::new(0, 1000.0).visibility(&w, p, up), 1.0);
+}
+
+#[test]
+fn ambient_occlusion_in_corner() {
+    let mut w = World::new();
+    w.objects.push(Shape::Plane(Plane::new()));
+    let mut wall = Plane::new();
+    wall.set_transform(MatrixChainer::new()
+                       .then(Matrix4x4::rotate_z(PI / 2.0))
+                       .then(Matrix4x4::translation(0.5, 0.0, 0.0))
+                       .finish());
+    w.objects.push(Shape::Plane(wall));
+    let up = Vector4D::new_vector(0.0, 1.0, 0.0);
+    let ao = AmbientOcclusion::new(256, 1.0);
+    let corner = ao.visibility(&w, Vector4D::new_point(0.49, 0.0001, 0.0), up);
+    let near = ao.visibility(&w, Vector4D::new_point(0.0, 0.0001, 0.0), up);
+    let far = ao.visibility(&w, Vector4D::new_point(-2.0, 0.0001, 0.0), up);
+    assert!(corner < near);
+    assert!(near < far);
+    assert!(corner > 0.3 && corner < 0.7);
+    assert_f64_eq!(far, 1.0);
+}
+
+#[test]
+fn ambient_occlusion_scales_ambient_in_shade_hit() {
+    let mut w = floor_and_ceiling();
+    let r = Ray::new(Vector4D::new_point(0.0, 1.0, -1.0), Vector4D::new_vector(0.0, -2.0f64.sqrt()/2.0, 2.0f64.sqrt()/2.0));
+    let i = Intersection { obj: Box::new(w.objects[0].clone()), t: 2.0f64.sqrt() };
+    let comps = r.prepare_computations(&i, &vec![]);
+    assert_color_eq!(shade_hit(&w, &comps, 0), Color::new(0.1, 0.1, 0.1));
+    w.ambient_occlusion = Some(AmbientOcclusion::new(16, 1.0));
+    assert_color_eq!(shade_hit(&w, &comps, 0), Color::new(0.1, 0.1, 0.1));
+    w.ambient_occlusion = Some(AmbientOcclusion::new(16, 1000.0));
+    assert_color_eq!(shade_hit(&w, &comps, 0), Color::BLACK);
+}
+
+#[test]
+fn ambient_occlusion_render_pass() {
+    let mut w = floor_and_ceiling();
+    w.ambient_occlusion = Some(AmbientOcclusion::new(16, 1000.0));
+    let mut c = Camera::new(11, 11, PI / 2.0);
+    c.transform = view_transformation(Vector4D::new_point(0.0, 1.0, 0.0),
+                                      Vector4D::new_point(0.0, 0.0, 0.0),
+                                      Vector4D::new_vector(0.0, 0.0, 1.0));
+    let config = RenderConfig::new(Integrator::AmbientOcclusion);
+    assert_color_eq!(render_pixel(&c, &w, &config, 5, 5), Color::BLACK);
+    w.objects.pop();
+    w.ambient_occlusion = None;
+    // the defaults are used without settings in the world
+    assert_color_eq!(render_pixel(&c, &w, &config, 5, 5), Color::WHITE);
+    w.objects.pop();
+    assert_color_eq!(render_pixel(&c, &w, &config, 5, 5), Color::WHITE);
+}
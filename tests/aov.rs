@@ -0,0 +1,121 @@
+use utils::*;
+use types::*;
+extern crate raytracer_challenge_rs;
+use raytracer_challenge_rs::{Canvas, RenderConfig, RenderRegion, Integrator, render_with_aovs, material_ids, id_color};
+
+mod common;
+use common::default_camera;
+
+#[test]
+fn aovs_of_hit_and_miss() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let aovs = render_with_aovs(&c, &w, &mut canvas, &Default::default());
+    assert_color_eq!(canvas.get_pixel(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+    let center = 5 * 11 + 5;
+    assert_f64_eq!(aovs.depth[center].unwrap(), 4.0);
+    assert_vector4d_eq!(aovs.normal[center].unwrap(), Vector4D::new_vector(0.0, 0.0, -1.0));
+    assert_color_eq!(aovs.albedo[center].unwrap(), Color::new(0.8, 1.0, 0.6));
+    assert_eq!(aovs.object_id[center], Some(0));
+    assert_eq!(aovs.material_id[center], Some(0));
+    assert!(aovs.depth[0].is_none());
+    assert!(aovs.object_id[0].is_none());
+    assert_color_eq!(aovs.depth_canvas().get_pixel(0, 0), Color::BLACK);
+    assert_color_eq!(aovs.normal_canvas().get_pixel(5, 5), Color::new(0.5, 0.5, 0.0));
+    assert_color_eq!(aovs.object_id_canvas().get_pixel(5, 5), id_color(0));
+}
+
+#[test]
+fn albedo_aov_uses_patterns() {
+    let mut w: World = Default::default();
+    let mut m = w.objects[0].get_material();
+    m.set_pattern(StripePattern::new(Color::new(1.0, 0.0, 0.0), Color::new(0.0, 0.0, 1.0)));
+    w.objects[0].set_material(&m);
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let aovs = render_with_aovs(&c, &w, &mut canvas, &Default::default());
+    // the center of the sphere is at x = 0, in the first stripe
+    assert_color_eq!(aovs.albedo[5 * 11 + 5].unwrap(), Color::new(1.0, 0.0, 0.0));
+}
+
+#[test]
+fn objects_with_same_material_share_id() {
+    let mut w: World = Default::default();
+    let mut s = Sphere::new();
+    s.set_material(w.objects[0].get_material());
+    s.set_transform(Matrix4x4::translation(3.0, 0.0, 0.0));
+    w.objects.push(Shape::Sphere(s));
+    assert_eq!(material_ids(&w), vec![0, 1, 0]);
+}
+
+#[test]
+fn materials_with_patterns_get_their_own_id() {
+    let mut w: World = Default::default();
+    let mut m = w.objects[0].get_material();
+    m.set_pattern(StripePattern::new(Color::new(1.0, 0.0, 0.0), Color::new(0.0, 0.0, 1.0)));
+    w.objects[0].set_material(&m);
+    w.objects[1].set_material(&m);
+    assert_eq!(material_ids(&w), vec![0, 1]);
+}
+
+#[test]
+fn object_id_of_shape_in_group() {
+    let mut w = World::new();
+    let mut s = Sphere::new();
+    s.set_transform(Matrix4x4::translation(3.0, 0.0, 0.0));
+    w.objects.push(Shape::Sphere(s));
+    let mut g = Group::new(7001);
+    g.add_child(Shape::Sphere(Sphere::new()));
+    w.objects.push(Shape::Group(g));
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let aovs = render_with_aovs(&c, &w, &mut canvas, &Default::default());
+    assert_eq!(aovs.object_id[5 * 11 + 5], Some(1));
+    // the group and the sphere beside it both have the default material
+    assert_eq!(aovs.material_id[5 * 11 + 5], Some(0));
+}
+
+#[test]
+fn aovs_only_for_region() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let config = RenderConfig {
+        region: Some(RenderRegion::new(0, 0, 5, 11)),
+        ..Default::default()
+    };
+    let aovs = render_with_aovs(&c, &w, &mut canvas, &config);
+    assert!(aovs.depth[5 * 11 + 4].is_some());
+    assert!(aovs.depth[5 * 11 + 5].is_none());
+    assert_color_eq!(canvas.get_pixel(5, 5), Color::BLACK);
+}
+
+#[test]
+fn aovs_with_path_tracing_and_ambient_occlusion() {
+    let w: World = Default::default();
+    let c = default_camera();
+    for integrator in [Integrator::PathTracing, Integrator::AmbientOcclusion].iter() {
+        let mut canvas = Canvas::new(11, 11);
+        let aovs = render_with_aovs(&c, &w, &mut canvas, &RenderConfig::new(*integrator));
+        assert_f64_eq!(aovs.depth[5 * 11 + 5].unwrap(), 4.0);
+        assert_eq!(aovs.object_id[5 * 11 + 5], Some(0));
+        assert!(aovs.depth[0].is_none());
+    }
+}
+
+#[test]
+fn write_aov_ppms() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let aovs = render_with_aovs(&c, &w, &mut canvas, &Default::default());
+    let prefix = std::env::temp_dir().join("raytracer_aov_test");
+    aovs.write_ppms(&prefix).unwrap();
+    for pass in ["depth", "normal", "albedo", "object_id", "material_id"].iter() {
+        let path = format!("{}_{}.ppm", prefix.to_string_lossy(), pass);
+        let image = Canvas::from_ppm(&path).unwrap();
+        assert_eq!(image.width, 11);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
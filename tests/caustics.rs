@@ -0,0 +1,114 @@
+use utils::*;
+use types::*;
+
+fn photon_at(x: f64, y: f64, z: f64) -> Photon {
+    Photon {
+        position: Vector4D::new_point(x, y, z),
+        incoming: Vector4D::new_vector(0.0, 1.0, 0.0),
+        power: Color::new(1.0, 1.0, 1.0),
+    }
+}
+
+#[test]
+fn photon_map_nearest_matches_brute_force() {
+    let mut sampler = Sampler::new(5);
+    let photons: Vec<Photon> = (0..500)
+        .map(|_| photon_at(sampler.next_f64() * 4.0, sampler.next_f64(), sampler.next_f64() * 4.0))
+        .collect();
+    let map = PhotonMap::new(photons.clone());
+    assert_eq!(map.photons.len(), 500);
+    for _ in 0..20 {
+        let p = Vector4D::new_point(sampler.next_f64() * 4.0, 0.5, sampler.next_f64() * 4.0);
+        let nearest = map.nearest(p, 10, 0.5);
+        let mut distances: Vec<f64> = photons.iter().map(|photon| (p - photon.position).norm())
+            .filter(|d| *d <= 0.5).collect();
+        distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        distances.truncate(10);
+        assert_eq!(nearest.len(), distances.len());
+        for (photon, d) in nearest.iter().zip(distances.iter()) {
+            assert_f64_eq!((p - photon.position).norm(), *d);
+        }
+    }
+}
+
+#[test]
+fn photon_map_irradiance_estimate() {
+    let mut photons = vec![photon_at(0.0, 0.0, 0.0), photon_at(0.1, 0.0, 0.0),
+                           photon_at(0.0, 0.0, 0.1), photon_at(2.0, 0.0, 0.0)];
+    let mut below = photon_at(0.0, 0.0, -0.1);
+    below.incoming = Vector4D::new_vector(0.0, -1.0, 0.0);
+    photons.push(below);
+    let map = PhotonMap::new(photons);
+    let up = Vector4D::new_vector(0.0, 1.0, 0.0);
+    // fewer photons than gather_count, so they are spread over max_radius
+    let e = map.irradiance_at(Vector4D::new_point(0.0, 0.0, 0.0), up);
+    let expected = 3.0 / (std::f64::consts::PI * map.max_radius * map.max_radius);
+    assert_color_eq!(e, Color::new(expected, expected, expected));
+    assert_color_eq!(map.irradiance_at(Vector4D::new_point(5.0, 0.0, 5.0), up), Color::BLACK);
+}
+
+fn glass_ball_over_floor() -> World {
+    let mut w = World::new();
+    w.light_source = LightSource::new(Color::WHITE, Vector4D::new_point(0.0, 10.0, 0.0));
+    let mut floor = Plane::new();
+    let mut m = floor.get_material();
+    m.specular = 0.0;
+    floor.set_material(m);
+    w.objects.push(Shape::Plane(floor));
+    let mut ball = Sphere::new_glass();
+    ball.set_transform(Matrix4x4::translation(0.0, 2.0, 0.0));
+    w.objects.push(Shape::Sphere(ball));
+    w
+}
+
+#[test]
+fn photon_map_only_keeps_photons_through_glass() {
+    let mut w = glass_ball_over_floor();
+    w.objects.pop();
+    w.build_photon_map(1000);
+    assert!(w.photon_map.unwrap().photons.is_empty());
+}
+
+#[test]
+fn glass_ball_focuses_caustic_on_floor() {
+    let mut w = glass_ball_over_floor();
+    w.build_photon_map(5000);
+    let map = w.photon_map.as_ref().unwrap();
+    assert!(!map.photons.is_empty());
+    for photon in map.photons.iter() {
+        assert_f64_eq!(photon.position.y, 0.0);
+    }
+
+    let floor_color = |x: f64| {
+        let r = Ray::new(Vector4D::new_point(x, 1.0, -1.0), Vector4D::new_vector(0.0, -2.0f64.sqrt()/2.0, 2.0f64.sqrt()/2.0));
+        let i = Intersection { obj: Box::new(w.objects[0].clone()), t: 2.0f64.sqrt() };
+        let comps = r.prepare_computations(&i, &vec![i.clone()]);
+        (w.caustics(&comps), shade_hit(&w, &comps, 5))
+    };
+    // right under the ball the floor is in shadow, but the ball focuses light there
+    let (caustic, color) = floor_color(0.0);
+    assert!(caustic.red > 1.0);
+    assert!(color.red > 1.0);
+    // out of the way of the ball there's no caustic
+    let (caustic, _) = floor_color(4.0);
+    assert_color_eq!(caustic, Color::BLACK);
+}
+
+#[test]
+fn glass_ball_in_group_focuses_caustic_on_floor() {
+    let mut w = glass_ball_over_floor();
+    let ball = w.objects.pop().unwrap();
+    let mut g = Group::new(7201);
+    g.add_child(ball);
+    w.objects.push(Shape::Group(g));
+    w.build_photon_map(5000);
+    let map = w.photon_map.as_ref().unwrap();
+    assert!(!map.photons.is_empty());
+    for photon in map.photons.iter() {
+        assert_f64_eq!(photon.position.y, 0.0);
+    }
+    let r = Ray::new(Vector4D::new_point(0.0, 1.0, -1.0), Vector4D::new_vector(0.0, -2.0f64.sqrt()/2.0, 2.0f64.sqrt()/2.0));
+    let i = Intersection::new(2.0f64.sqrt(), w.objects[0].clone());
+    let comps = r.prepare_computations(&i, &vec![i.clone()]);
+    assert!(w.caustics(&comps).red > 1.0);
+}
@@ -73,3 +73,64 @@ fn canvas_write_ppm() {
     assert_eq!(lines[5], "0 0 0 0 0 0 0 0 0 0 0 0 0 0 255"); 
 
 }
+
+#[test]
+fn canvas_from_ppm_plain() {
+    let ppm = "P3\n# a comment\n2 2\n255\n255 0 0  0 255 0\n0 0 255  51 102 153\n";
+    let c = Canvas::from_ppm_str(ppm).unwrap();
+    assert_eq!(c.width, 2);
+    assert_eq!(c.height, 2);
+    assert_color_eq!(c.get_pixel(0, 0), Color::new(1.0, 0.0, 0.0));
+    assert_color_eq!(c.get_pixel(1, 0), Color::new(0.0, 1.0, 0.0));
+    assert_color_eq!(c.get_pixel(0, 1), Color::new(0.0, 0.0, 1.0));
+    assert_color_eq!(c.get_pixel(1, 1), Color::new(0.2, 0.4, 0.6));
+}
+
+#[test]
+fn canvas_from_ppm_scales_max_value() {
+    let ppm = "P3\n1 1\n100\n100 50 0\n";
+    let c = Canvas::from_ppm_str(ppm).unwrap();
+    assert_color_eq!(c.get_pixel(0, 0), Color::new(1.0, 0.5, 0.0));
+}
+
+#[test]
+fn canvas_from_ppm_binary() {
+    let mut ppm = b"P6\n2 1\n255\n".to_vec();
+    ppm.extend_from_slice(&[255, 0, 0, 0, 0, 255]);
+    let c = Canvas::from_ppm_bytes(&ppm).unwrap();
+    assert_color_eq!(c.get_pixel(0, 0), Color::new(1.0, 0.0, 0.0));
+    assert_color_eq!(c.get_pixel(1, 0), Color::new(0.0, 0.0, 1.0));
+}
+
+#[test]
+fn canvas_from_ppm_round_trip() {
+    let mut c = Canvas::new(5, 3);
+    c.set_pixel(0, 0, &Color::new(1.0, 0.0, 0.0));
+    c.set_pixel(2, 1, &Color::new(0.0, 0.2, 0.0));
+    c.set_pixel(4, 2, &Color::new(0.0, 0.0, 1.0));
+    let read = Canvas::from_ppm_str(&c.write_ppm_str()).unwrap();
+    assert_color_eq!(read.get_pixel(0, 0), Color::new(1.0, 0.0, 0.0));
+    assert_color_eq!(read.get_pixel(2, 1), Color::new(0.0, 0.2, 0.0));
+    assert_color_eq!(read.get_pixel(4, 2), Color::new(0.0, 0.0, 1.0));
+}
+
+#[test]
+fn canvas_from_ppm_bad_input() {
+    assert!(Canvas::from_ppm_str("P5\n1 1\n255\n").is_err());
+    assert!(Canvas::from_ppm_str("P3\n2 2\n255\n1 2 3\n").is_err());
+}
+
+#[test]
+fn canvas_from_ppm_huge_header() {
+    // would overflow the size of the pixel buffer
+    assert!(Canvas::from_ppm_str("P3\n18446744073709551615 18446744073709551615\n255\n0 0 0\n").is_err());
+    // fits in memory maths but not in the file
+    assert!(Canvas::from_ppm_str("P6\n100000 100000\n255\n\x00\x00\x00").is_err());
+}
+
+#[test]
+fn canvas_from_ppm_zero_size() {
+    assert!(Canvas::from_ppm_str("P3\n0 2\n255\n").is_err());
+    assert!(Canvas::from_ppm_str("P3\n2 0\n255\n").is_err());
+    assert!(Canvas::from_ppm_bytes(b"P6\n0 0\n255\n").is_err());
+}
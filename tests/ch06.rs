@@ -173,3 +173,132 @@ fn test_raytrace_sphere_lighting() {
     canvas.write_ppm("ch6.ppm").unwrap();
 }
 
+
+#[test]
+fn noise_bump_no_amplitude_keeps_normal() {
+    let mut s = Sphere::new();
+    let mut m: Material = Default::default();
+    m.set_bump(NoiseBump::new(4.0, 0.0));
+    s.set_material(m);
+    let r = Ray::new(Vector4D::new_point(0.3, 0.2, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let xs = s.intersect(&r);
+    let sc = r.prepare_computations(&xs[0], &xs);
+    assert_vector4d_eq!(sc.normalv, s.normal_at(sc.point));
+}
+
+#[test]
+fn noise_bump_perturbs_normal() {
+    let mut s = Sphere::new();
+    let mut m: Material = Default::default();
+    m.set_bump(NoiseBump::new(4.0, 0.5));
+    s.set_material(m);
+    let r = Ray::new(Vector4D::new_point(0.3, 0.2, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let xs = s.intersect(&r);
+    let sc = r.prepare_computations(&xs[0], &xs);
+    let geometric = s.normal_at(sc.point);
+    assert!(!sc.normalv.eq(&geometric));
+    assert_f64_eq!(sc.normalv.norm(), 1.0);
+    assert!(sc.normalv.dot(geometric) > 0.0);
+    assert_f64_eq!(sc.normalv.w, 0.0);
+}
+
+#[test]
+fn flat_normal_map_keeps_normal() {
+    let mut s = Sphere::new();
+    s.set_transform(Matrix4x4::scaling(2.0, 1.0, 1.0));
+    let flat = Color::new(0.5, 0.5, 1.0);
+    let mut m: Material = Default::default();
+    m.set_bump(NormalMap::new(Box::new(UvCheckersPattern::new(1.0, 1.0, flat, flat)), UvMapping::Spherical));
+    s.set_material(m);
+    let r = Ray::new(Vector4D::new_point(0.3, 0.2, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let xs = s.intersect(&r);
+    let sc = r.prepare_computations(&xs[0], &xs);
+    assert_vector4d_eq!(sc.normalv, s.normal_at(sc.point));
+}
+
+#[test]
+fn normal_map_tilts_plane_normal() {
+    let mut p = Plane::new();
+    let tilted = Color::new(0.75, 0.5, 1.0);
+    let mut m: Material = Default::default();
+    m.set_bump(NormalMap::new(Box::new(UvCheckersPattern::new(1.0, 1.0, tilted, tilted)), UvMapping::Planar));
+    p.set_material(m);
+    p.set_transform(Matrix4x4::rotate_y(PI/2.0));
+    let r = Ray::new(Vector4D::new_point(0.0, 1.0, 0.0), Vector4D::new_vector(0.0, -1.0, 0.0));
+    let xs = p.intersect(&r);
+    let sc = r.prepare_computations(&xs[0], &xs);
+    // tangent (u) direction of the plane is x, rotated to -z in the world
+    assert_vector4d_eq!(sc.normalv, Vector4D::new_vector(0.0, 0.89443, -0.44721));
+}
+
+#[test]
+fn default_material_uses_phong() {
+    let m: Material = Default::default();
+    assert_eq!(m.shading, ShadingModel::Phong);
+}
+
+#[test]
+fn microfacet_lighting_facing_the_light() {
+    let p = Vector4D::new_point(0.0, 0.0, 0.0);
+    let m = Material::new_microfacet(Color::WHITE, 0.0, 1.0);
+    let eyev = Vector4D::new_vector(0.0, 0.0, -1.0);
+    let normalv = Vector4D::new_vector(0.0, 0.0, -1.0);
+    let light = LightSource::new(Color::WHITE, Vector4D::new_point(0.0, 0.0, -10.0));
+    let result = lighting(m, &Shape::Sphere(Sphere::new()), light, p, eyev, normalv, false);
+    // ambient 0.1 + diffuse (1 - 0.04) + specular 0.04 * D(1/PI) / 4 * PI
+    assert_eq!(result, Color::new(1.07, 1.07, 1.07));
+}
+
+#[test]
+fn microfacet_lighting_behind_and_in_shadow() {
+    let p = Vector4D::new_point(0.0, 0.0, 0.0);
+    let m = Material::new_microfacet(Color::WHITE, 0.0, 0.5);
+    let eyev = Vector4D::new_vector(0.0, 0.0, -1.0);
+    let normalv = Vector4D::new_vector(0.0, 0.0, -1.0);
+    let behind = LightSource::new(Color::WHITE, Vector4D::new_point(0.0, 0.0, 10.0));
+    let result = lighting(m.clone(), &Shape::Sphere(Sphere::new()), behind, p, eyev, normalv, false);
+    assert_eq!(result, Color::new(0.1, 0.1, 0.1));
+    let front = LightSource::new(Color::WHITE, Vector4D::new_point(0.0, 0.0, -10.0));
+    let result = lighting(m, &Shape::Sphere(Sphere::new()), front, p, eyev, normalv, true);
+    assert_eq!(result, Color::new(0.1, 0.1, 0.1));
+}
+
+#[test]
+fn microfacet_metal_reflects_its_color() {
+    let p = Vector4D::new_point(0.0, 0.0, 0.0);
+    let mut m = Material::new_microfacet(Color::new(1.0, 0.5, 0.0), 1.0, 0.3);
+    m.ambient = 0.0;
+    let eyev = Vector4D::new_vector(0.0, 0.0, -1.0);
+    let normalv = Vector4D::new_vector(0.0, 0.0, -1.0);
+    let light = LightSource::new(Color::WHITE, Vector4D::new_point(0.0, 0.0, -10.0));
+    let result = lighting(m, &Shape::Sphere(Sphere::new()), light, p, eyev, normalv, false);
+    // no diffuse, the highlight is tinted by the base color
+    assert!(result.red > 0.0);
+    assert_f64_eq!(result.green, result.red * 0.5);
+    assert_f64_eq!(result.blue, 0.0);
+}
+
+#[test]
+fn microfacet_smooth_surface_has_sharper_highlight() {
+    let p = Vector4D::new_point(0.0, 0.0, 0.0);
+    let normalv = Vector4D::new_vector(0.0, 0.0, -1.0);
+    let light = LightSource::new(Color::WHITE, Vector4D::new_point(0.0, 10.0, -10.0));
+    let mirror = Vector4D::new_vector(0.0, -2.0f64.sqrt()/2.0, -2.0f64.sqrt()/2.0);
+    let off = Vector4D::new_vector(0.0, 0.0, -1.0);
+    let smooth = Material::new_microfacet(Color::WHITE, 0.0, 0.1);
+    let rough = Material::new_microfacet(Color::WHITE, 0.0, 0.8);
+    let s = Shape::Sphere(Sphere::new());
+    let smooth_mirror = lighting(smooth.clone(), &s, light, p, mirror, normalv, false);
+    let rough_mirror = lighting(rough.clone(), &s, light, p, mirror, normalv, false);
+    let smooth_off = lighting(smooth, &s, light, p, off, normalv, false);
+    let rough_off = lighting(rough, &s, light, p, off, normalv, false);
+    assert!(smooth_mirror.red > rough_mirror.red);
+    assert!(smooth_off.red < rough_off.red);
+}
+
+#[test]
+fn fresnel_schlick_limits() {
+    let f0 = Color::new(0.04, 0.04, 0.04);
+    assert_eq!(fresnel_schlick(1.0, f0), f0);
+    assert_eq!(fresnel_schlick(0.0, f0), Color::WHITE);
+}
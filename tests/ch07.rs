@@ -360,3 +360,209 @@ fn test_raytrace_with_camera_multiple_spheres() {
     c.transform = view_transformation(from, to, up); 
     render(&c, &world, &mut canvas);
 }
+
+#[test]
+fn color_at_miss_default_background_black() {
+    let world: World = Default::default();
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 1.0, 0.0));
+    assert_color_eq!(color_at(&world, r, 0), Color::BLACK);
+}
+
+#[test]
+fn color_at_miss_constant_background() {
+    let world = World { background: Background::Color(Color::new(0.2, 0.3, 0.9)), ..Default::default() };
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 1.0, 0.0));
+    assert_color_eq!(color_at(&world, r, 0), Color::new(0.2, 0.3, 0.9));
+}
+
+#[test]
+fn gradient_background() {
+    let bg = Background::Gradient(Color::white(), Color::blue());
+    assert_color_eq!(bg.color_at(Vector4D::new_vector(0.0, -1.0, 0.0)), Color::white());
+    assert_color_eq!(bg.color_at(Vector4D::new_vector(0.0, 2.0, 0.0)), Color::blue());
+    assert_color_eq!(bg.color_at(Vector4D::new_vector(1.0, 0.0, 0.0)), Color::new(0.5, 0.5, 1.0));
+}
+
+#[test]
+fn equirectangular_background() {
+    let image = UvAlignCheckPattern::new(Color::white(), Color::red(), Color::green(), Color::blue(), Color::BLACK);
+    let bg = Background::Equirectangular(Box::new(image));
+    // straight ahead is the center of the image
+    assert_color_eq!(bg.color_at(Vector4D::new_vector(0.0, 0.0, -1.0)), Color::white());
+    // turning right from straight ahead (towards -x) moves right in the image until the right
+    // edge is directly behind
+    assert_color_eq!(bg.color_at(Vector4D::new_vector(-0.1, 0.99, 0.1)), Color::green());
+    assert_color_eq!(bg.color_at(Vector4D::new_vector(-0.1, -0.99, 0.1)), Color::BLACK);
+    assert_color_eq!(bg.color_at(Vector4D::new_vector(0.1, 0.99, 0.1)), Color::red());
+    assert_color_eq!(bg.color_at(Vector4D::new_vector(0.1, -0.99, 0.1)), Color::blue());
+}
+
+#[test]
+fn cube_map_background() {
+    let red = Color::red();
+    let yellow = Color::new(1.0, 1.0, 0.0);
+    let brown = Color::new(1.0, 0.5, 0.0);
+    let green = Color::green();
+    let cyan = Color::new(0.0, 1.0, 1.0);
+    let blue = Color::blue();
+    let purple = Color::new(1.0, 0.0, 1.0);
+    let white = Color::white();
+    // the same faces as the CubeMapPattern test, main color then upper left, upper right, bottom
+    // left and bottom right corners
+    let faces: [BoxedUvPattern; 6] = [
+        Box::new(UvAlignCheckPattern::new(yellow, cyan, red, blue, brown)),
+        Box::new(UvAlignCheckPattern::new(cyan, red, yellow, brown, green)),
+        Box::new(UvAlignCheckPattern::new(red, yellow, purple, green, white)),
+        Box::new(UvAlignCheckPattern::new(green, purple, cyan, white, blue)),
+        Box::new(UvAlignCheckPattern::new(brown, cyan, purple, red, yellow)),
+        Box::new(UvAlignCheckPattern::new(purple, brown, green, blue, white)),
+    ];
+    let bg = Background::CubeMap(faces);
+    // seen from inside the cube every face is mirrored left to right compared to the pattern, so
+    // each corner shows the corner across from the one the pattern has at that point
+    let cases = [
+        (Vector4D::new_vector(-1.0, 0.0, 0.0), yellow),
+        (Vector4D::new_vector(-1.0, 0.9, -0.9), red),
+        (Vector4D::new_vector(-1.0, 0.9, 0.9), cyan),
+        (Vector4D::new_vector(-1.0, -0.9, -0.9), brown),
+        (Vector4D::new_vector(-1.0, -0.9, 0.9), blue),
+        (Vector4D::new_vector(0.0, 0.0, 1.0), cyan),
+        (Vector4D::new_vector(-0.9, 0.9, 1.0), yellow),
+        (Vector4D::new_vector(0.9, 0.9, 1.0), red),
+        (Vector4D::new_vector(-0.9, -0.9, 1.0), green),
+        (Vector4D::new_vector(0.9, -0.9, 1.0), brown),
+        (Vector4D::new_vector(1.0, 0.0, 0.0), red),
+        (Vector4D::new_vector(1.0, 0.9, 0.9), purple),
+        (Vector4D::new_vector(1.0, 0.9, -0.9), yellow),
+        (Vector4D::new_vector(1.0, -0.9, 0.9), white),
+        (Vector4D::new_vector(1.0, -0.9, -0.9), green),
+        (Vector4D::new_vector(0.0, 0.0, -1.0), green),
+        (Vector4D::new_vector(0.9, 0.9, -1.0), cyan),
+        (Vector4D::new_vector(-0.9, 0.9, -1.0), purple),
+        (Vector4D::new_vector(0.9, -0.9, -1.0), blue),
+        (Vector4D::new_vector(-0.9, -0.9, -1.0), white),
+        (Vector4D::new_vector(0.0, 1.0, 0.0), brown),
+        (Vector4D::new_vector(-0.9, 1.0, -0.9), purple),
+        (Vector4D::new_vector(0.9, 1.0, -0.9), cyan),
+        (Vector4D::new_vector(-0.9, 1.0, 0.9), yellow),
+        (Vector4D::new_vector(0.9, 1.0, 0.9), red),
+        (Vector4D::new_vector(0.0, -1.0, 0.0), purple),
+        (Vector4D::new_vector(-0.9, -1.0, 0.9), green),
+        (Vector4D::new_vector(0.9, -1.0, 0.9), brown),
+        (Vector4D::new_vector(-0.9, -1.0, -0.9), white),
+        (Vector4D::new_vector(0.9, -1.0, -0.9), blue),
+        // the length of the direction doesn't matter
+        (Vector4D::new_vector(-4.5, -4.5, 5.0), green),
+    ];
+    for (d, c) in cases.iter() {
+        assert_color_eq!(bg.color_at(*d), *c);
+    }
+}
+
+#[test]
+fn reflected_ray_sees_background() {
+    let mut world = World::new();
+    world.background = Background::Color(Color::new(0.0, 0.0, 0.5));
+    let mut mirror = Plane::new();
+    let m = Material {
+        color: Color::BLACK,
+        ambient: 0.0,
+        diffuse: 0.0,
+        specular: 0.0,
+        reflective: 1.0,
+        ..Default::default()
+    };
+    mirror.set_material(m);
+    world.objects.push(Shape::Plane(mirror));
+    let r = Ray::new(Vector4D::new_point(0.0, 1.0, -1.0), Vector4D::new_vector(0.0, -1.0, 1.0).normalized());
+    assert_color_eq!(color_at(&world, r, 1), Color::new(0.0, 0.0, 0.5));
+}
+
+#[test]
+fn shade_hit_adds_emissive() {
+    let mut w : World = Default::default();
+    let mut m = w.objects[0].get_material();
+    assert_color_eq!(m.emissive, Color::BLACK);
+    m.emissive = Color::new(0.2, 0.2, 0.2);
+    w.objects[0].set_material(&m);
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let i = Intersection { obj: Box::new(w.objects[0].clone()), t: 4.0 };
+    let c = r.prepare_computations(&i, &vec![]);
+    let color = shade_hit(&w, &c, 0);
+    assert_color_eq!(color, Color::new(0.580661193, 0.675826491, 0.4854958948));
+}
+
+// A white floor lit only by a glowing ball of radius 1 three units above the origin
+fn floor_under_glowing_ball(blocked: bool) -> (World, Shape) {
+    let mut w = World::new();
+    w.light_source = LightSource::new(Color::BLACK, Vector4D::new_point(-10.0, 10.0, -10.0));
+    w.area_light_samples = 4096;
+    let mut floor = Plane::new();
+    let mut m = floor.get_material();
+    m.ambient = 0.0;
+    m.diffuse = 1.0;
+    m.specular = 0.0;
+    floor.set_material(m);
+    let floor = Shape::Plane(floor);
+    w.objects.push(floor.clone());
+    let mut ball = Sphere::new();
+    ball.set_transform(Matrix4x4::translation(0.0, 3.0, 0.0));
+    let mut m = ball.get_material();
+    m.emissive = Color::WHITE;
+    ball.set_material(m);
+    w.objects.push(Shape::Sphere(ball));
+    if blocked {
+        let mut cover = Cube::new();
+        cover.set_transform(MatrixChainer::new()
+                            .then(Matrix4x4::scaling(2.0, 0.1, 2.0))
+                            .then(Matrix4x4::translation(0.0, 1.5, 0.0))
+                            .finish());
+        w.objects.push(Shape::Cube(cover));
+    }
+    (w, floor)
+}
+
+#[test]
+fn emissive_ball_lights_floor() {
+    let (mut w, floor) = floor_under_glowing_ball(false);
+    let r = Ray::new(Vector4D::new_point(0.0, 1.0, -1.0), Vector4D::new_vector(0.0, -2.0f64.sqrt()/2.0, 2.0f64.sqrt()/2.0));
+    let i = Intersection { obj: Box::new(floor), t: 2.0f64.sqrt() };
+    let c = r.prepare_computations(&i, &vec![]);
+    // irradiance from a sphere of radiance 1 straight above is PI * (1/3)^2
+    let color = shade_hit(&w, &c, 0);
+    assert!((color.red - 1.0 / 9.0).abs() < 0.005);
+    assert_f64_eq!(color.red, color.blue);
+    w.area_light_samples = 0;
+    assert_color_eq!(shade_hit(&w, &c, 0), Color::BLACK);
+}
+
+#[test]
+fn emissive_group_glows_and_lights_floor() {
+    let (mut w, floor) = floor_under_glowing_ball(false);
+    // the same ball, but as the emissive child of a group that moves it up
+    let mut g = Group::new(7101);
+    let mut ball = Sphere::new();
+    let mut m = ball.get_material();
+    m.emissive = Color::WHITE;
+    ball.set_material(m);
+    g.add_child(Shape::Sphere(ball));
+    g.set_transform(Matrix4x4::translation(0.0, 3.0, 0.0));
+    w.objects[1] = Shape::Group(g);
+    let r = Ray::new(Vector4D::new_point(0.0, 1.0, -1.0), Vector4D::new_vector(0.0, -2.0f64.sqrt()/2.0, 2.0f64.sqrt()/2.0));
+    let i = Intersection::new(2.0f64.sqrt(), floor);
+    let c = r.prepare_computations(&i, &vec![]);
+    let color = shade_hit(&w, &c, 0);
+    assert!((color.red - 1.0 / 9.0).abs() < 0.005, "{:?}", color);
+    // looking at the ball shows its glow
+    let r = Ray::new(Vector4D::new_point(0.0, 3.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    assert_color_eq!(color_at(&w, r, 0), Color::WHITE);
+}
+
+#[test]
+fn emissive_ball_blocked_from_floor() {
+    let (w, floor) = floor_under_glowing_ball(true);
+    let r = Ray::new(Vector4D::new_point(0.0, 1.0, -1.0), Vector4D::new_vector(0.0, -2.0f64.sqrt()/2.0, 2.0f64.sqrt()/2.0));
+    let i = Intersection { obj: Box::new(floor), t: 2.0f64.sqrt() };
+    let c = r.prepare_computations(&i, &vec![]);
+    assert_color_eq!(shade_hit(&w, &c, 0), Color::BLACK);
+}
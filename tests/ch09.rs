@@ -89,3 +89,121 @@ fn test_raytrace_with_camera_floor_and_wall() {
     render(&c, &world, &mut canvas);
 }
 
+
+// A unit disk in the xz plane, defined outside of the types crate
+#[derive(Debug, Clone)]
+struct Disk {
+    transform: Matrix4x4,
+    material: Material,
+    parent: Option<usize>,
+}
+
+impl Disk {
+    fn new() -> Disk {
+        Disk {
+            transform: Matrix4x4::new(),
+            material: Default::default(),
+            parent: None,
+        }
+    }
+}
+
+impl Intersectable for Disk {
+    fn intersect(&self, ray: &Ray) -> Intersections {
+        let ray = ray.transform(&self.get_transform().inverse());
+        if ray.direction.y.abs() < EPSILON {
+            return vec![];
+        }
+        let t = -ray.origin.y / ray.direction.y;
+        let p = ray.at_t(t);
+        if p.x.powi(2) + p.z.powi(2) > 1.0 {
+            return vec![];
+        }
+        vec![Intersection { obj: Box::new(Shape::custom(self.clone())), t }]
+    }
+    fn eq(&self, other: &Shape) -> bool {
+        match other {
+            Shape::Custom(ref o) => self.get_transform().eq(&o.get_transform()),
+            _ => false
+        }
+    }
+    fn set_transform(&mut self, m: Matrix4x4) {
+        self.transform = m;
+    }
+    fn get_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+    fn normal_at_local(&self, _obj_point: Vector4D) -> Vector4D {
+        Vector4D::new_vector(0.0, 1.0, 0.0)
+    }
+    fn get_material(&self) -> Material {
+        self.material.clone()
+    }
+    fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+    fn get_parent(&self) -> Option<Group> {
+        self.parent.map(group_by_id)
+    }
+    fn set_parent(&mut self, parent: Group) {
+        self.parent = Some(parent.id);
+    }
+    fn get_bounds(&self) -> Bounds {
+        Bounds {
+            min_point: Vector4D::new_point(-1.0, 0.0, -1.0),
+            max_point: Vector4D::new_point(1.0, 0.0, 1.0)
+        }
+    }
+}
+
+#[test]
+fn test_custom_shape_intersect_world() {
+    let mut world = World::new();
+    let mut disk = Shape::custom(Disk::new());
+    disk.set_transform(Matrix4x4::translation(0.0, -1.0, 0.0));
+    world.objects.push(disk.clone());
+
+    let hit_ray = Ray::new(Vector4D::new_point(0.5, 1.0, 0.0), Vector4D::new_vector(0.0, -1.0, 0.0));
+    let xs = world.intersect(&hit_ray);
+    assert_eq!(xs.len(), 1);
+    assert_f64_eq!(xs[0].t, 2.0);
+    assert!(xs[0].obj.eq(&disk));
+
+    let miss_ray = Ray::new(Vector4D::new_point(1.5, 1.0, 0.0), Vector4D::new_vector(0.0, -1.0, 0.0));
+    assert_eq!(world.intersect(&miss_ray).len(), 0);
+}
+
+#[test]
+fn test_custom_shape_shading() {
+    let mut world = World::new();
+    world.light_source = LightSource::new(Color::new(1.0, 1.0, 1.0), Vector4D::new_point(0.0, 10.0, 0.0));
+    let mut disk = Shape::custom(Disk::new());
+    let mut m = Material::new(Color::new(1.0, 0.0, 0.0));
+    m.specular = 0.0;
+    disk.set_material(&m);
+    world.objects.push(disk);
+
+    let ray = Ray::new(Vector4D::new_point(0.0, 1.0, 0.0), Vector4D::new_vector(0.0, -1.0, 0.0));
+    let xs = world.intersect(&ray);
+    let sc = ray.prepare_computations(&xs[0], &xs);
+    assert_vector4d_eq!(sc.normalv, Vector4D::new_vector(0.0, 1.0, 0.0));
+    let c = color_at(&world, ray, 0);
+    assert_color_eq!(c, Color::new(1.0, 0.0, 0.0));
+}
+
+#[test]
+fn test_custom_shape_in_group_transformed_after_add_child() {
+    let mut group = Group::new(9001);
+    let mut disk = Shape::custom(Disk::new());
+    disk.set_transform(Matrix4x4::translation(5.0, 0.0, 0.0));
+    group.add_child(disk);
+    group.set_transform(Matrix4x4::scaling(2.0, 2.0, 2.0));
+    let disk = &group.children[0];
+    let p = disk.world_to_object(Vector4D::new_point(10.0, 0.0, 0.0));
+    assert_vector4d_eq!(p, Vector4D::new_point(0.0, 0.0, 0.0));
+
+    group.set_transform(Matrix4x4::rotate_x(PI));
+    let disk = &group.children[0];
+    let n = disk.normal_at(Vector4D::new_point(5.0, 0.0, 0.0));
+    assert_vector4d_eq!(n, Vector4D::new_vector(0.0, -1.0, 0.0));
+}
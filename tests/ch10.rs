@@ -461,3 +461,206 @@ fn test_schlick_n2_gt_n1() {
     assert_f64_eq!(reflectance, 0.48873);
 }
 
+
+// A pattern defined outside of the types crate
+#[derive(Debug, Clone)]
+struct SolidRedPattern {
+    transform: Matrix4x4,
+}
+
+impl Patternable for SolidRedPattern {
+    fn get_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Matrix4x4) {
+        self.transform = transform;
+    }
+
+    fn pattern_at(&self, _point: Vector4D) -> Color {
+        Color::red()
+    }
+}
+
+#[test]
+fn test_material_with_user_pattern() {
+    let mut m = Material { ambient: 1.0, diffuse: 0.0, specular: 0.0, ..Default::default() };
+    m.set_pattern(SolidRedPattern { transform: Matrix4x4::new() });
+    let m2 = m.clone();
+    let eyev = Vector4D::new_vector(0.0, 0.0, -1.0);
+    let normalv = Vector4D::new_vector(0.0, 0.0, -1.0);
+    let light = LightSource::new(Color::new(1.0, 1.0, 1.0), Vector4D::new_point(0.0, 0.0, -10.0));
+    let c = lighting(m2, &Shape::Sphere(Sphere::new()), light, Vector4D::new_point(0.9, 0.0, 0.0), eyev, normalv, false);
+    assert_color_eq!(c, Color::red());
+}
+
+#[test]
+fn test_material_with_boxed_builtin_pattern() {
+    let m = Material {
+        ambient: 1.0,
+        diffuse: 0.0,
+        specular: 0.0,
+        pattern: Some(Box::new(StripePattern::new(Color::white(), Color::BLACK))),
+        ..Default::default()
+    };
+    let eyev = Vector4D::new_vector(0.0, 0.0, -1.0);
+    let normalv = Vector4D::new_vector(0.0, 0.0, -1.0);
+    let light = LightSource::new(Color::new(1.0, 1.0, 1.0), Vector4D::new_point(0.0, 0.0, -10.0));
+    let c1 = lighting(m.clone(), &Shape::Sphere(Sphere::new()), light, Vector4D::new_point(0.9, 0.0, 0.0), eyev, normalv, false);
+    let c2 = lighting(m, &Shape::Sphere(Sphere::new()), light, Vector4D::new_point(1.1, 0.0, 0.0), eyev, normalv, false);
+    assert_color_eq!(c1, Color::white());
+    assert_color_eq!(c2, Color::BLACK);
+}
+
+#[test]
+fn test_nested_pattern_stripes_of_patterns() {
+    let stripes_1 = StripePattern::new(Color::red(), Color::green());
+    let mut stripes_2 = StripePattern::new(Color::blue(), Color::white());
+    stripes_2.set_transform(Matrix4x4::scaling(0.5, 0.5, 0.5));
+    let p = CheckeredPattern::new_nested(stripes_1, stripes_2);
+    // checker at (0, 0, 0) uses the first stripes
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.5, 0.0, 0.0)), Color::red());
+    // checker at (0, 0, 1) uses the second stripes which are scaled down by half
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.25, 0.0, 1.5)), Color::blue());
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.75, 0.0, 1.5)), Color::white());
+}
+
+#[test]
+fn test_nested_pattern_mixed_color_and_pattern() {
+    let p = StripePattern::new_nested(Color::red(), CheckeredPattern::new(Color::blue(), Color::green()));
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.5, 0.0, 0.0)), Color::red());
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(1.5, 0.0, 0.0)), Color::green());
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(1.5, 1.0, 0.0)), Color::blue());
+}
+
+#[test]
+fn test_blended_pattern_average() {
+    let p = BlendedPattern::new(StripePattern::new(Color::white(), Color::BLACK), 
+                                StripePattern::new(Color::red(), Color::blue()));
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.5, 0.0, 0.0)), Color::new(1.0, 0.5, 0.5));
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(1.5, 0.0, 0.0)), Color::new(0.0, 0.0, 0.5));
+}
+
+#[test]
+fn test_blended_pattern_weight_and_sub_transform() {
+    let mut rotated = StripePattern::new(Color::red(), Color::BLACK);
+    rotated.set_transform(Matrix4x4::rotate_y(PI/2.0));
+    let mut p = BlendedPattern::new_weighted(StripePattern::new(Color::white(), Color::BLACK), rotated, 0.25);
+    p.set_transform(Matrix4x4::scaling(2.0, 2.0, 2.0));
+    let s = Sphere::new();
+    // pattern space (0.25, 0, 0.25), the rotated stripes see x = -0.25 so they are black
+    let c = p.pattern_at_object(&s, Vector4D::new_point(0.5, 0.0, 0.5));
+    assert_color_eq!(c, Color::new(0.75, 0.75, 0.75));
+    // pattern space (0.25, 0, -0.75), the rotated stripes see x = 0.75 so they are red
+    let c = p.pattern_at_object(&s, Vector4D::new_point(0.5, 0.0, -1.5));
+    assert_color_eq!(c, Color::new(1.0, 0.75, 0.75));
+}
+
+#[test]
+fn test_perlin_noise_zero_at_lattice_points() {
+    assert_f64_eq!(perlin_noise(0.0, 0.0, 0.0), 0.0);
+    assert_f64_eq!(perlin_noise(1.0, 2.0, 3.0), 0.0);
+    assert_f64_eq!(perlin_noise(-4.0, 7.0, -2.0), 0.0);
+}
+
+#[test]
+fn test_perlin_noise_range_and_deterministic() {
+    let mut not_zero = false;
+    for i in 0..1000 {
+        let p = Vector4D::new_point(i as f64 * 0.137, i as f64 * -0.291, i as f64 * 0.053);
+        let n = perlin_noise_at(p);
+        assert!((-1.0..=1.0).contains(&n));
+        assert_f64_eq!(n, perlin_noise_at(p));
+        let t = turbulence(p, 4, 0.5);
+        assert!((0.0..=1.0).contains(&t));
+        if n.abs() > EPSILON {
+            not_zero = true;
+        }
+    }
+    assert!(not_zero);
+}
+
+#[test]
+fn test_perturbed_pattern_no_scale_is_inner_pattern() {
+    let mut p = PerturbedPattern::new(StripePattern::new(Color::white(), Color::BLACK));
+    p.scale = 0.0;
+    p.octaves = 3;
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.37, 0.5, 0.2)), Color::white());
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(1.37, 0.5, 0.2)), Color::BLACK);
+}
+
+#[test]
+fn test_perturbed_pattern_moves_boundaries() {
+    let p = PerturbedPattern::new(GradientPattern::new(Color::white(), Color::BLACK));
+    let point = Vector4D::new_point(0.37, 0.51, 0.23);
+    let inner = GradientPattern::new(Color::white(), Color::BLACK).pattern_at(point);
+    assert!(p.pattern_at(point) != inner);
+}
+
+#[test]
+fn test_marble_and_wood_between_colors() {
+    let marble = MarblePattern::new(Color::white(), Color::BLACK);
+    let wood = WoodPattern::new(Color::new(0.6, 0.4, 0.2), Color::new(0.3, 0.2, 0.1));
+    for i in 0..100 {
+        let p = Vector4D::new_point(i as f64 * 0.31, i as f64 * 0.17, i as f64 * -0.23);
+        let m = marble.pattern_at(p);
+        assert!(m.red >= 0.0 && m.red <= 1.0);
+        assert_f64_eq!(m.red, m.green);
+        let w = wood.pattern_at(p);
+        assert!(w.red >= 0.3 && w.red <= 0.6);
+        assert_f64_eq!(w.green * 3.0, w.red * 2.0);
+    }
+}
+
+#[test]
+fn test_radial_gradient_pattern() {
+    let p = RadialGradientPattern::new(Color::white(), Color::BLACK);
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.0, 5.0, 0.0)), Color::white());
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.3, 5.0, 0.4)), Color::new(0.5, 0.5, 0.5));
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.0, 0.0, 1.25)), Color::new(0.75, 0.75, 0.75));
+
+    let p = RadialGradientPattern::new_spherical(Color::white(), Color::BLACK);
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.0, 0.5, 0.0)), Color::new(0.5, 0.5, 0.5));
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.0, 0.3, 0.4)), Color::new(0.5, 0.5, 0.5));
+}
+
+#[test]
+fn test_ring_gradient_pattern() {
+    let p = RingGradientPattern::new(Color::white(), Color::BLACK);
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.0, 0.0, 0.0)), Color::white());
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.25, 0.0, 0.0)), Color::new(0.5, 0.5, 0.5));
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.0, 0.0, 0.5)), Color::BLACK);
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.75, 0.0, 0.0)), Color::new(0.5, 0.5, 0.5));
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.6, 0.0, 0.8)), Color::white());
+}
+
+#[test]
+fn test_checkered_pattern_sizes_per_axis() {
+    let p = CheckeredPattern::new_sized(Color::white(), Color::BLACK, 2.0, 1.0, 0.5);
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(1.5, 0.0, 0.0)), Color::white());
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(2.5, 0.0, 0.0)), Color::BLACK);
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.0, 1.5, 0.0)), Color::BLACK);
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.0, 0.0, 0.75)), Color::BLACK);
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.0, 0.0, 1.25)), Color::white());
+}
+
+#[test]
+fn test_checkered_pattern_clamps_sizes() {
+    let p = CheckeredPattern::new_sized(Color::white(), Color::BLACK, 0.0, -1.0, f64::NAN);
+    assert!(p.sizes.iter().all(|s| *s > 0.0));
+    assert!(p.pattern_at(Vector4D::new_point(0.5, 0.5, 0.5)).red.is_finite());
+    let mut p = CheckeredPattern::new(Color::white(), Color::BLACK);
+    p.sizes = [0.0, 1.0, 1.0];
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(0.00025, 0.0, 0.0)), Color::white());
+}
+
+#[test]
+fn test_solid_pattern() {
+    let mut p = SolidPattern::new(Color::red());
+    p.set_transform(Matrix4x4::scaling(3.0, 2.0, 1.0));
+    assert_color_eq!(p.pattern_at(Vector4D::new_point(1.0, 2.0, 3.0)), Color::red());
+    let s = Sphere::new();
+    assert_color_eq!(p.pattern_at_object(&s, Vector4D::new_point(-4.0, 0.5, 0.0)), Color::red());
+    let stripes = StripePattern::new_nested(SolidPattern::new(Color::green()), SolidPattern::new(Color::blue()));
+    assert_color_eq!(stripes.pattern_at(Vector4D::new_point(1.5, 0.0, 0.0)), Color::blue());
+}
@@ -535,3 +535,213 @@ fn test_render_gold_glass_ball() {
 
     canvas.write_ppm("ch11_gold_glass_ball.ppm").unwrap();
 }
+
+#[test]
+fn test_sampler_is_deterministic() {
+    let mut a = Sampler::new(42);
+    let mut b = Sampler::new(42);
+    let mut c = Sampler::new(43);
+    let mut differs = false;
+    for _ in 0..100 {
+        let x = a.next_f64();
+        assert!((0.0..1.0).contains(&x));
+        assert_f64_eq!(x, b.next_f64());
+        if !f64_eq(x, c.next_f64()) {
+            differs = true;
+        }
+    }
+    assert!(differs);
+}
+
+#[test]
+fn test_sample_cone_stays_in_cone() {
+    let axis = Vector4D::new_vector(0.0, 1.0, 1.0).normalized();
+    let mut sampler = Sampler::new(7);
+    for _ in 0..100 {
+        let dir = sample_cone(axis, PI / 8.0, sampler.next_f64(), sampler.next_f64());
+        assert_f64_eq!(dir.norm(), 1.0);
+        assert!(dir.dot(axis) >= (PI / 8.0).cos() - EPSILON);
+    }
+}
+
+#[test]
+fn test_glossy_reflection_of_uniform_background() {
+    let mut w = World::new();
+    w.background = Background::Color(Color::new(0.2, 0.4, 0.6));
+    let mut plane = Plane::new();
+    let mut m = plane.get_material();
+    m.reflective = 0.5;
+    m.reflect_roughness = 0.5;
+    plane.set_material(m);
+    w.objects.push(Shape::Plane(plane));
+    let r = Ray::new(Vector4D::new_point(0.0, 1.0, -1.0), Vector4D::new_vector(0.0, -2.0f64.sqrt()/2.0, 2.0f64.sqrt()/2.0));
+    let i = Intersection { t: 2.0f64.sqrt(), obj: Box::new(w.objects[0].clone()) };
+    let comps = r.prepare_computations(&i, &vec![]);
+    // every spread ray still sees the same background
+    assert_color_eq!(w.reflected_color(&comps, 1), Color::new(0.1, 0.2, 0.3));
+}
+
+#[test]
+fn test_glossy_color_spreads_rays_on_one_side() {
+    let mut w = World::new();
+    w.background = Background::Gradient(Color::BLACK, Color::WHITE);
+    let origin = Vector4D::new_point(0.0, 0.0, 0.0);
+    let up = Vector4D::new_vector(0.0, 1.0, 0.0);
+    let grazing = Vector4D::new_vector(1.0, 0.05, 0.0).normalized();
+    assert_color_eq!(w.glossy_color(origin, up, up, 0.0, 16, 1, None), Color::WHITE);
+    let rough = w.glossy_color(origin, up, up, 0.5, 16, 1, None);
+    assert!(rough.red < 1.0 && rough.red > 0.5);
+    // rays that would go below the surface get mirrored back above it
+    let reflected = w.glossy_color(origin, grazing, up, 1.0, 32, 1, None);
+    assert!(reflected.red >= 0.5);
+    // frosted refraction stays below the surface
+    let refracted = w.glossy_color(origin, -grazing, -up, 1.0, 32, 1, None);
+    assert!(refracted.red <= 0.5);
+}
+
+#[test]
+fn test_prepare_computations_finds_media() {
+    let mut glass = Sphere::new();
+    let mut m = glass.get_material();
+    m.transparency = 1.0;
+    m.refractive_index = 1.5;
+    glass.set_material(m);
+    let shape = Shape::Sphere(glass);
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let xs = vec![Intersection { t: 4.0, obj: Box::new(shape.clone()) },
+                  Intersection { t: 6.0, obj: Box::new(shape.clone()) }];
+    let entering = r.prepare_computations(&xs[0], &xs);
+    assert!(entering.n1_medium.is_none());
+    assert!(entering.n2_medium.unwrap().eq(&shape));
+    let leaving = r.prepare_computations(&xs[1], &xs);
+    assert!(leaving.n1_medium.unwrap().eq(&shape));
+    assert!(leaving.n2_medium.is_none());
+}
+
+#[test]
+fn test_material_transmittance() {
+    let mut m: Material = Default::default();
+    assert!(!m.is_absorbing());
+    assert_color_eq!(m.transmittance(10.0), Color::WHITE);
+    m.absorption = Color::new(1.0, 0.5, 0.0);
+    m.absorption_density = 1.0;
+    assert!(m.is_absorbing());
+    assert_color_eq!(m.transmittance(2.0), Color::new((-2.0f64).exp(), (-1.0f64).exp(), 1.0));
+    assert_color_eq!(m.transmittance(f64::INFINITY), Color::new(0.0, 0.0, 1.0));
+}
+
+fn refracted_color_through_absorbing_ball(radius: f64) -> Color {
+    let mut w = World::new();
+    w.background = Background::Color(Color::WHITE);
+    let mut ball = Sphere::new();
+    ball.set_transform(Matrix4x4::scaling(radius, radius, radius));
+    let mut m = ball.get_material();
+    m.ambient = 0.0;
+    m.diffuse = 0.0;
+    m.specular = 0.0;
+    m.transparency = 1.0;
+    m.refractive_index = 1.5;
+    m.absorption = Color::new(0.5, 0.5, 0.5);
+    m.absorption_density = 1.0;
+    ball.set_material(m);
+    let shape = Shape::Sphere(ball);
+    w.objects.push(shape.clone());
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let xs = vec![Intersection { t: 5.0 - radius, obj: Box::new(shape.clone()) },
+                  Intersection { t: 5.0 + radius, obj: Box::new(shape) }];
+    let comps = r.prepare_computations(&xs[0], &xs);
+    w.refracted_color(&comps, 5)
+}
+
+#[test]
+fn test_refracted_color_absorbed_by_thickness() {
+    // straight through the center, the light travels the diameter of the ball
+    let thin = refracted_color_through_absorbing_ball(1.0);
+    let thick = refracted_color_through_absorbing_ball(2.0);
+    assert_color_eq!(thin, Color::new(0.36788, 0.36788, 0.36788));
+    assert_color_eq!(thick, Color::new(0.13534, 0.13534, 0.13534));
+}
+
+#[test]
+fn test_color_and_hit_at() {
+    let w: World = Default::default();
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let (color, hit) = color_and_hit_at(&w, r, 5);
+    assert_color_eq!(color, color_at(&w, r, 5));
+    let hit = hit.unwrap();
+    assert_f64_eq!(hit.t, 4.0);
+    assert_eq!(hit.object, Some(0));
+    assert_vector4d_eq!(hit.normalv, Vector4D::new_vector(0.0, 0.0, -1.0));
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 1.0, 0.0));
+    let (color, hit) = color_and_hit_at(&w, r, 5);
+    assert_color_eq!(color, Color::BLACK);
+    assert!(hit.is_none());
+}
+
+#[test]
+fn test_dispersion_index_at() {
+    let cauchy = Dispersion::Cauchy { a: 1.5, b: 0.01 };
+    assert_f64_eq!(cauchy.index_at(0.5), 1.54);
+    assert!((dispersions::BK7_GLASS.index_at(0.5876) - 1.5168).abs() < 0.001);
+    assert!(dispersions::DIAMOND.index_at(dispersions::BLUE_WAVELENGTH) >
+            dispersions::DIAMOND.index_at(dispersions::RED_WAVELENGTH));
+    let mut m: Material = Default::default();
+    m.set_dispersion(cauchy);
+    assert_f64_eq!(m.refractive_index, cauchy.index_at(dispersions::GREEN_WAVELENGTH));
+    assert_f64_eq!(m.refractive_index_at(0.5), 1.54);
+}
+
+fn refracted_color_into_glass_floor(dispersion: Option<Dispersion>) -> Color {
+    let mut w = World::new();
+    w.background = Background::Gradient(Color::BLACK, Color::WHITE);
+    let mut floor = Plane::new();
+    let mut m = floor.get_material();
+    m.transparency = 1.0;
+    m.refractive_index = dispersions::DIAMOND.index_at(dispersions::GREEN_WAVELENGTH);
+    m.dispersion = dispersion;
+    floor.set_material(m);
+    let shape = Shape::Plane(floor);
+    w.objects.push(shape.clone());
+    let r = Ray::new(Vector4D::new_point(0.0, 1.0, -1.0), Vector4D::new_vector(0.0, -2.0f64.sqrt()/2.0, 2.0f64.sqrt()/2.0));
+    let xs = vec![Intersection { t: 2.0f64.sqrt(), obj: Box::new(shape) }];
+    let comps = r.prepare_computations(&xs[0], &xs);
+    w.refracted_color(&comps, 5)
+}
+
+#[test]
+fn test_refracted_color_with_dispersion() {
+    let plain = refracted_color_into_glass_floor(None);
+    assert_f64_eq!(plain.red, plain.blue);
+    // blue bends the most towards the normal so it sees the darker bottom of the background
+    let split = refracted_color_into_glass_floor(Some(dispersions::DIAMOND));
+    assert!(split.red > split.green);
+    assert!(split.green > split.blue);
+    assert_f64_eq!(split.green, plain.green);
+}
+
+fn rays_through_glass_floors(dispersion: Option<Dispersion>) -> usize {
+    let mut w = World::new();
+    for i in 0..4 {
+        let mut floor = Plane::new();
+        floor.set_transform(Matrix4x4::translation(0.0, -(i as f64), 0.0));
+        let mut m = floor.get_material();
+        m.transparency = 1.0;
+        m.refractive_index = dispersions::DIAMOND.index_at(dispersions::GREEN_WAVELENGTH);
+        m.dispersion = dispersion;
+        floor.set_material(m);
+        w.objects.push(Shape::Plane(floor));
+    }
+    let r = Ray::new(Vector4D::new_point(0.0, 1.0, -1.0), Vector4D::new_vector(0.0, -2.0f64.sqrt()/2.0, 2.0f64.sqrt()/2.0));
+    let before = rays_traced();
+    color_at(&w, r, 5);
+    rays_traced() - before
+}
+
+#[test]
+fn test_dispersion_splits_light_once() {
+    let plain = rays_through_glass_floors(None);
+    // the first floor traces a ray per channel, the floors below only follow that color
+    let split = rays_through_glass_floors(Some(dispersions::DIAMOND));
+    assert!(split > plain);
+    assert!(split <= 3 * plain);
+}
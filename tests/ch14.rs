@@ -126,6 +126,31 @@ fn test_normal_child_object() {
 
 
 
+#[test]
+fn test_group_bounds() {
+    let g = Group::new(310);
+    assert!(g.get_bounds().is_empty());
+    assert!(bounding_sphere(&Shape::Group(g)).is_none());
+
+    let mut s = Sphere::new();
+    s.set_transform(Matrix4x4::translation(2.0, 5.0, -3.0));
+    let mut c = Cylinder::new();
+    c.minimum = -2.0;
+    c.maximum = 2.0;
+    c.closed = true;
+    c.set_transform(Matrix4x4::scaling(0.5, 1.0, 0.5));
+    let mut g = Group::new(311);
+    g.add_child(Shape::Sphere(s));
+    g.add_child(Shape::Cylinder(c));
+    let bounds = g.get_bounds();
+    assert_vector4d_eq!(bounds.min_point, Vector4D::new_point(-0.5, -2.0, -4.0));
+    assert_vector4d_eq!(bounds.max_point, Vector4D::new_point(3.0, 6.0, 0.5));
+
+    g.add_child(Shape::Plane(Plane::new()));
+    assert!(g.get_bounds().is_infinite());
+    assert!(bounding_sphere(&Shape::Group(g)).is_none());
+}
+
 fn hexagon_corner() -> Shape {
     let mut corner = Sphere::new();
     let mut obj_mat: Material = Default::default();
@@ -0,0 +1,11 @@
+use types::*;
+use std::f64::consts::PI;
+
+// The 11x11 camera the render tests look through, 5 units in front of the origin
+pub fn default_camera() -> Camera {
+    let mut c = Camera::new(11, 11, PI/2.0);
+    c.transform = view_transformation(Vector4D::new_point(0.0, 0.0, -5.0),
+                                      Vector4D::new_point(0.0, 0.0, 0.0),
+                                      Vector4D::new_vector(0.0, 1.0, 0.0));
+    c
+}
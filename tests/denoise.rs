@@ -0,0 +1,145 @@
+use utils::*;
+use types::*;
+extern crate raytracer_challenge_rs;
+use raytracer_challenge_rs::{Canvas, AovBuffers, DenoiseSettings};
+
+const SIZE: usize = 16;
+
+// Left half red facing the camera, right half blue facing sideways, with sampling noise
+fn noisy_canvas(noise: f64) -> Canvas {
+    let mut sampler = Sampler::new(7);
+    let mut canvas = Canvas::new(SIZE, SIZE);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let base = if x < SIZE / 2 { Color::new(0.8, 0.1, 0.1) } else { Color::new(0.1, 0.1, 0.8) };
+            let n = (sampler.next_f64() - 0.5) * 2.0 * noise;
+            canvas.set_pixel(x, y, &(base + Color::new(n, n, n)));
+        }
+    }
+    canvas
+}
+
+fn guide() -> AovBuffers {
+    let mut aovs = AovBuffers::new(SIZE, SIZE);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let i = y * SIZE + x;
+            let left = x < SIZE / 2;
+            aovs.depth[i] = Some(if left { 4.0 } else { 6.0 });
+            aovs.normal[i] = Some(if left { Vector4D::new_vector(0.0, 0.0, -1.0) } else { Vector4D::new_vector(-1.0, 0.0, 0.0) });
+            aovs.albedo[i] = Some(if left { Color::new(0.8, 0.1, 0.1) } else { Color::new(0.1, 0.1, 0.8) });
+        }
+    }
+    aovs
+}
+
+// Average squared distance from the colors without noise
+fn error(canvas: &Canvas) -> f64 {
+    let clean = noisy_canvas(0.0);
+    let mut total = 0.0;
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let d = canvas.get_pixel(x, y) - clean.get_pixel(x, y);
+            total += d.red * d.red + d.green * d.green + d.blue * d.blue;
+        }
+    }
+    total / (SIZE * SIZE) as f64
+}
+
+#[test]
+fn denoise_keeps_flat_image() {
+    let canvas = noisy_canvas(0.0);
+    let denoised = canvas.denoise(None, &DenoiseSettings::default()).unwrap();
+    assert_color_eq!(denoised.get_pixel(2, 2), Color::new(0.8, 0.1, 0.1));
+    assert_color_eq!(denoised.get_pixel(13, 13), Color::new(0.1, 0.1, 0.8));
+    let denoised = canvas.denoise(Some(&guide()), &DenoiseSettings::default()).unwrap();
+    assert_color_eq!(denoised.get_pixel(7, 5), Color::new(0.8, 0.1, 0.1));
+    assert_color_eq!(denoised.get_pixel(8, 5), Color::new(0.1, 0.1, 0.8));
+}
+
+#[test]
+fn denoise_reduces_noise() {
+    let canvas = noisy_canvas(0.2);
+    let before = error(&canvas);
+    let after = error(&canvas.denoise(None, &DenoiseSettings::default()).unwrap());
+    assert!(after < before / 4.0, "{} before, {} after", before, after);
+    let guided = error(&canvas.denoise(Some(&guide()), &DenoiseSettings::default()).unwrap());
+    assert!(guided < before / 4.0, "{} before, {} after", before, guided);
+}
+
+#[test]
+fn guided_denoise_keeps_edges() {
+    // with a loose color sigma the colors alone don't stop the halves bleeding into each other
+    let settings = DenoiseSettings {
+        sigma_color: 10.0,
+        ..Default::default()
+    };
+    let canvas = noisy_canvas(0.05);
+    let blurred = canvas.denoise(None, &settings).unwrap();
+    assert!(blurred.get_pixel(7, 8).blue > 0.3);
+    let guided = canvas.denoise(Some(&guide()), &settings).unwrap();
+    let left = guided.get_pixel(7, 8);
+    let right = guided.get_pixel(8, 8);
+    assert!((left.red - 0.8).abs() < 0.05 && (left.blue - 0.1).abs() < 0.05, "{:?}", left);
+    assert!((right.blue - 0.8).abs() < 0.05 && (right.red - 0.1).abs() < 0.05, "{:?}", right);
+}
+
+#[test]
+fn guided_denoise_keeps_background_apart() {
+    let mut canvas = Canvas::new(4, 1);
+    let mut aovs = AovBuffers::new(4, 1);
+    for x in 0..2 {
+        canvas.set_pixel(x, 0, &Color::WHITE);
+        aovs.normal[x] = Some(Vector4D::new_vector(0.0, 0.0, -1.0));
+    }
+    let settings = DenoiseSettings {
+        sigma_color: 10.0,
+        ..Default::default()
+    };
+    let denoised = canvas.denoise(Some(&aovs), &settings).unwrap();
+    assert_color_eq!(denoised.get_pixel(1, 0), Color::WHITE);
+    assert_color_eq!(denoised.get_pixel(2, 0), Color::BLACK);
+}
+
+#[test]
+fn denoise_rejects_guide_of_other_size() {
+    let canvas = noisy_canvas(0.0);
+    assert!(canvas.denoise(Some(&AovBuffers::new(SIZE, SIZE - 1)), &DenoiseSettings::default()).is_err());
+}
+
+#[test]
+fn denoise_with_zero_sigmas_keeps_image() {
+    let settings = DenoiseSettings {
+        sigma_spatial: 0.0,
+        sigma_color: 0.0,
+        sigma_normal: -1.0,
+        sigma_albedo: 0.0,
+        sigma_depth: 0.0,
+        ..Default::default()
+    };
+    let canvas = noisy_canvas(0.2);
+    let denoised = canvas.denoise(Some(&guide()), &settings).unwrap();
+    for (x, y) in [(0, 0), (7, 8), (15, 15)].iter() {
+        assert_color_eq!(denoised.get_pixel(*x, *y), canvas.get_pixel(*x, *y));
+    }
+}
+
+#[test]
+fn guided_denoise_weighs_normals_by_distance() {
+    // the halves only differ in their normals, tilted by a bit on the right
+    let mut canvas = Canvas::new(2, 1);
+    canvas.set_pixel(0, 0, &Color::WHITE);
+    let mut aovs = AovBuffers::new(2, 1);
+    aovs.normal[0] = Some(Vector4D::new_vector(0.0, 0.0, -1.0));
+    aovs.normal[1] = Some(Vector4D::new_vector(0.6, 0.0, -0.8));
+    let settings = DenoiseSettings {
+        sigma_spatial: 1000.0,
+        sigma_color: 1000.0,
+        sigma_normal: 0.5,
+        ..Default::default()
+    };
+    // the normals are sqrt(0.4) apart, exp(-0.4 / (2 * 0.5^2)) = exp(-0.8)
+    let w = (-0.8f64).exp();
+    let denoised = canvas.denoise(Some(&aovs), &settings).unwrap();
+    assert_color_eq!(denoised.get_pixel(0, 0), Color::WHITE * (1.0 / (1.0 + w)));
+}
@@ -0,0 +1,95 @@
+use utils::*;
+use types::*;
+use std::f64::consts::PI;
+
+#[test]
+fn henyey_greenstein_phase() {
+    assert_f64_eq!(henyey_greenstein(0.3, 0.0), 1.0 / (4.0 * PI));
+    // integrates to 1 over the sphere, and g > 0 favors forward scattering
+    let n = 10000;
+    let total: f64 = (0..n).map(|i| {
+        let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / n as f64;
+        henyey_greenstein(cos_theta, 0.6) * 2.0 * PI * (2.0 / n as f64)
+    }).sum();
+    assert!((total - 1.0).abs() < 0.001);
+    assert!(henyey_greenstein(1.0, 0.6) > henyey_greenstein(-1.0, 0.6));
+}
+
+#[test]
+fn fog_absorbs_light_to_the_hit() {
+    let mut w: World = Default::default();
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let clear = color_at(&w, r, 0);
+    w.fog = Some(Medium::new(0.1, 0.0, 0.0));
+    // the sphere is hit after 4 units
+    assert_color_eq!(color_at(&w, r, 0), clear * (-0.4f64).exp());
+}
+
+#[test]
+fn fog_absorbs_background() {
+    let mut w = World::new();
+    w.background = Background::Color(Color::WHITE);
+    let mut fog = Medium::new(0.01, 0.0, 0.0);
+    fog.max_distance = 50.0;
+    w.fog = Some(fog);
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, 0.0), Vector4D::new_vector(0.0, 0.0, 2.0));
+    let expected = (-0.5f64).exp();
+    assert_color_eq!(color_at(&w, r, 0), Color::new(expected, expected, expected));
+}
+
+#[test]
+fn volume_absorbs_light_inside_shape() {
+    let mut w: World = Default::default();
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let clear = color_at(&w, r, 0);
+    let mut smoke = Sphere::new();
+    smoke.set_transform(MatrixChainer::new()
+                        .then(Matrix4x4::scaling(0.5, 0.5, 0.5))
+                        .then(Matrix4x4::translation(0.0, 0.0, -3.0))
+                        .finish());
+    w.volumes.push(Volume::new(Shape::Sphere(smoke), Medium::new(0.3, 0.0, 0.0)));
+    // the ray goes through the diameter of the smoke
+    assert_color_eq!(color_at(&w, r, 0), clear * (-0.3f64).exp());
+    assert_color_eq!(media_transmittance(&w, &r, 10.0), Color::new(0.74082, 0.74082, 0.74082));
+    // the volume isn't an object, so it doesn't show up
+    assert_eq!(w.intersect(&r).len(), 4);
+}
+
+fn foggy_world(anisotropy: f64) -> World {
+    let mut w = World::new();
+    w.light_source = LightSource::new(Color::WHITE, Vector4D::new_point(0.0, 0.0, 10.0));
+    let mut fog = Medium::new(0.0, 0.05, anisotropy);
+    fog.max_distance = 5.0;
+    w.fog = Some(fog);
+    w
+}
+
+#[test]
+fn fog_scatters_light_into_rays() {
+    let w = foggy_world(0.0);
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, 0.0), Vector4D::new_vector(1.0, 0.0, 0.0));
+    let color = color_at(&w, r, 0);
+    assert!(color.red > 0.0);
+    assert_f64_eq!(color.red, color.blue);
+}
+
+#[test]
+fn forward_scattering_fog_glows_towards_the_light() {
+    let w = foggy_world(0.7);
+    let towards = color_at(&w, Ray::new(Vector4D::new_point(0.0, 0.0, 0.0), Vector4D::new_vector(0.0, 0.0, 1.0)), 0);
+    let away = color_at(&w, Ray::new(Vector4D::new_point(0.0, 0.0, 0.0), Vector4D::new_vector(0.0, 0.0, -1.0)), 0);
+    assert!(towards.red > 10.0 * away.red);
+}
+
+#[test]
+fn shadowed_fog_has_no_in_scattering() {
+    let mut w = foggy_world(0.0);
+    let mut wall = Cube::new();
+    wall.set_transform(MatrixChainer::new()
+                       .then(Matrix4x4::scaling(100.0, 100.0, 0.1))
+                       .then(Matrix4x4::translation(0.0, 0.0, 8.0))
+                       .finish());
+    w.objects.push(Shape::Cube(wall));
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, 0.0), Vector4D::new_vector(1.0, 0.0, 0.0));
+    assert_color_eq!(color_at(&w, r, 0), Color::BLACK);
+}
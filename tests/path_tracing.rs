@@ -0,0 +1,144 @@
+use utils::*;
+use types::*;
+use std::f64::consts::PI;
+extern crate raytracer_challenge_rs;
+use raytracer_challenge_rs::{Canvas, RenderConfig, Integrator, render_with_config, render_pixel};
+
+mod common;
+use common::default_camera;
+
+#[test]
+fn default_render_config() {
+    let config: RenderConfig = Default::default();
+    assert_eq!(config.integrator, Integrator::Whitted);
+    assert_eq!(config.max_depth, 3);
+    assert_eq!(config.samples_per_pixel, 1);
+    assert_eq!(RenderConfig::new(Integrator::PathTracing).integrator, Integrator::PathTracing);
+}
+
+#[test]
+fn render_with_config_whitted() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    render_with_config(&c, &w, &mut canvas, &Default::default());
+    assert_color_eq!(canvas.get_pixel(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+    // every pixel gets rendered, including the last row and column
+    assert_color_eq!(canvas.get_pixel(10, 10), color_at(&w, ray_for_pixel(&c, 10, 10), 3));
+}
+
+#[test]
+fn samples_per_pixel_average_over_pixel() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let config = RenderConfig {
+        samples_per_pixel: 16,
+        ..Default::default()
+    };
+    // the middle of the sphere is smooth so the average is close to the center ray
+    let color = render_pixel(&c, &w, &config, 5, 5);
+    assert!((color.red - 0.38066).abs() < 0.02);
+    // pixels on the edge of the sphere mix the sphere with the background
+    let single: RenderConfig = Default::default();
+    let edge = (0..11).find(|x| {
+        let center = render_pixel(&c, &w, &single, *x, 5);
+        let average = render_pixel(&c, &w, &config, *x, 5);
+        (center.red - average.red).abs() > 0.05
+    });
+    assert!(edge.is_some());
+}
+
+fn mirror_world() -> World {
+    let mut w = World::new();
+    w.background = Background::Gradient(Color::new(0.0, 0.0, 1.0), Color::new(1.0, 1.0, 0.0));
+    let mut mirror = Plane::new();
+    let mut m = mirror.get_material();
+    m.ambient = 0.0;
+    m.diffuse = 0.0;
+    m.specular = 0.0;
+    m.reflective = 1.0;
+    mirror.set_material(m);
+    w.objects.push(Shape::Plane(mirror));
+    w
+}
+
+#[test]
+fn path_trace_mirror_matches_whitted() {
+    let w = mirror_world();
+    let r = Ray::new(Vector4D::new_point(0.0, 1.0, -1.0), Vector4D::new_vector(0.0, -0.6, 0.8));
+    let mut sampler = Sampler::new(1);
+    assert_color_eq!(path_trace(&w, r, 3, &mut sampler), color_at(&w, r, 3));
+}
+
+#[test]
+fn path_trace_glass_converges_to_whitted() {
+    let mut w = World::new();
+    w.background = Background::Gradient(Color::new(0.0, 0.0, 1.0), Color::new(1.0, 1.0, 0.0));
+    let mut ball = Sphere::new_glass();
+    let mut m = ball.get_material();
+    m.ambient = 0.0;
+    m.diffuse = 0.0;
+    m.specular = 0.0;
+    m.reflective = 1.0;
+    ball.set_material(m);
+    w.objects.push(Shape::Sphere(ball));
+    let r = Ray::new(Vector4D::new_point(0.0, 0.5, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let mut sampler = Sampler::new(2);
+    let samples = 4000;
+    let mut total = Color::BLACK;
+    for _ in 0..samples {
+        total = total + path_trace(&w, r, 5, &mut sampler);
+    }
+    let average = total * (1.0 / samples as f64);
+    let whitted = color_at(&w, r, 5);
+    assert!((average.red - whitted.red).abs() < 0.02);
+    assert!((average.blue - whitted.blue).abs() < 0.02);
+}
+
+#[test]
+fn path_trace_bleeds_color() {
+    let mut w = World::new();
+    w.light_source = LightSource::new(Color::WHITE, Vector4D::new_point(-2.0, 5.0, 0.0));
+    let mut floor = Plane::new();
+    let mut m = floor.get_material();
+    m.specular = 0.0;
+    floor.set_material(m);
+    w.objects.push(Shape::Plane(floor));
+    let mut wall = Plane::new();
+    wall.set_transform(MatrixChainer::new()
+                       .then(Matrix4x4::rotate_z(PI / 2.0))
+                       .then(Matrix4x4::translation(0.5, 0.0, 0.0))
+                       .finish());
+    let mut m = Material::new(Color::new(1.0, 0.0, 0.0));
+    m.specular = 0.0;
+    wall.set_material(m);
+    w.objects.push(Shape::Plane(wall));
+
+    let r = Ray::new(Vector4D::new_point(0.0, 1.0, -1.0), Vector4D::new_vector(0.0, -2.0f64.sqrt()/2.0, 2.0f64.sqrt()/2.0));
+    let whitted = color_at(&w, r, 3);
+    assert_f64_eq!(whitted.red, whitted.green);
+    let mut sampler = Sampler::new(3);
+    let samples = 500;
+    let mut total = Color::BLACK;
+    for _ in 0..samples {
+        total = total + path_trace(&w, r, 3, &mut sampler);
+    }
+    let average = total * (1.0 / samples as f64);
+    // the red wall lights up the white floor next to it
+    assert!(average.red > average.green + 0.05);
+}
+
+#[test]
+fn path_trace_sees_emissive_objects() {
+    let mut w = World::new();
+    w.light_source = LightSource::new(Color::BLACK, Vector4D::new_point(-10.0, 10.0, -10.0));
+    let mut lamp = Sphere::new();
+    let mut m = lamp.get_material();
+    m.emissive = Color::new(2.0, 1.0, 0.5);
+    m.diffuse = 0.0;
+    lamp.set_material(m);
+    w.objects.push(Shape::Sphere(lamp));
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let mut sampler = Sampler::new(4);
+    assert_color_eq!(path_trace(&w, r, 3, &mut sampler), Color::new(2.0, 1.0, 0.5));
+}
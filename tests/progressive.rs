@@ -0,0 +1,96 @@
+use utils::*;
+use types::*;
+use std::time::Duration;
+extern crate raytracer_challenge_rs;
+use raytracer_challenge_rs::{Canvas, RenderConfig, Integrator, ProgressiveConfig, SampleBuffer, render_progressive};
+
+mod common;
+use common::default_camera;
+
+#[test]
+fn default_progressive_config() {
+    let config: ProgressiveConfig = Default::default();
+    assert_eq!(config.max_samples, Some(64));
+    assert_eq!(config.time_budget, None);
+    assert_eq!(config.noise_threshold, None);
+    assert_eq!(config.render.samples_per_pixel, 1);
+    let config = ProgressiveConfig::new(RenderConfig::new(Integrator::PathTracing));
+    assert_eq!(config.render.integrator, Integrator::PathTracing);
+}
+
+#[test]
+fn progressive_render_calls_back_after_each_pass() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let config = ProgressiveConfig {
+        render: RenderConfig {
+            samples_per_pixel: 2,
+            ..Default::default()
+        },
+        max_samples: Some(8),
+        ..Default::default()
+    };
+    let mut seen = vec![];
+    let last = render_progressive(&c, &w, &mut canvas, &config, |image, progress| {
+        seen.push((progress.passes, progress.samples_per_pixel, image.get_pixel(5, 5)));
+    });
+    assert_eq!(seen.len(), 4);
+    assert_eq!(seen.iter().map(|s| s.1).collect::<Vec<_>>(), vec![2, 4, 6, 8]);
+    assert_eq!(last.passes, 4);
+    assert_eq!(last.samples_per_pixel, 8);
+    // the image is there from the first pass, and the canvas ends up with the last estimate
+    assert!(seen[0].2.red > 0.2);
+    assert_color_eq!(canvas.get_pixel(5, 5), seen[3].2);
+    assert_color_eq!(canvas.get_pixel(0, 0), Color::BLACK);
+}
+
+#[test]
+fn sample_buffer_averages_passes() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let config: RenderConfig = Default::default();
+    let mut buffer = SampleBuffer::new(11, 11);
+    assert!(buffer.noise().is_infinite());
+    buffer.render_pass(&c, &w, &config);
+    buffer.render_pass(&c, &w, &config);
+    assert_eq!(buffer.samples, 2);
+    let estimate = buffer.estimate();
+    assert_color_eq!(estimate.get_pixel(5, 5), buffer.sum[5 * 11 + 5] * 0.5);
+    // the background is black everywhere and the middle of the sphere is smooth
+    assert!(buffer.noise() < 0.1);
+}
+
+#[test]
+fn progressive_render_stops_at_noise_threshold() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let config = ProgressiveConfig {
+        render: RenderConfig::new(Integrator::PathTracing),
+        max_samples: Some(1000),
+        noise_threshold: Some(0.002),
+        ..Default::default()
+    };
+    let mut noise = vec![];
+    let last = render_progressive(&c, &w, &mut canvas, &config, |_, progress| noise.push(progress.noise));
+    assert!(last.samples_per_pixel < 1000);
+    assert!(last.noise <= 0.002);
+    assert!(noise[0].is_infinite());
+    assert!(noise.len() > 2);
+    assert!(noise[noise.len() - 2] > 0.002);
+}
+
+#[test]
+fn progressive_render_stops_at_time_budget() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let config = ProgressiveConfig {
+        max_samples: None,
+        time_budget: Some(Duration::from_millis(0)),
+        ..Default::default()
+    };
+    let last = render_progressive(&c, &w, &mut canvas, &config, |_, _| ());
+    assert_eq!(last.passes, 1);
+}
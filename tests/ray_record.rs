@@ -0,0 +1,112 @@
+use utils::*;
+use types::*;
+extern crate raytracer_challenge_rs;
+use raytracer_challenge_rs::{RenderConfig, Integrator, record_pixel, render_pixel};
+
+mod common;
+use common::default_camera;
+
+fn world_with_glass_floor() -> World {
+    let mut w: World = Default::default();
+    let mut floor = Plane::new();
+    floor.set_transform(Matrix4x4::translation(0.0, -1.0, 0.0));
+    let mut m = floor.get_material();
+    m.reflective = 0.5;
+    m.transparency = 0.5;
+    m.refractive_index = 1.5;
+    floor.set_material(m);
+    w.objects.push(Shape::Plane(floor));
+    w
+}
+
+#[test]
+fn recording_is_off_by_default() {
+    assert!(!recording_rays());
+    let w: World = Default::default();
+    color_at(&w, Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0)), 3);
+    assert!(take_recorded_rays().is_empty());
+}
+
+#[test]
+fn record_a_hit() {
+    let w: World = Default::default();
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let record = record_ray(&w, r, 3);
+    assert!(!recording_rays());
+    assert_eq!(record.kind, RayKind::Camera);
+    assert_eq!(record.remaining, 3);
+    let hit = record.hit.as_ref().unwrap();
+    assert_eq!(hit.object, "sphere");
+    assert_f64_eq!(hit.t, 4.0);
+    assert_vector4d_eq!(hit.normalv, Vector4D::new_vector(0.0, 0.0, -1.0));
+    assert_eq!(record.shadow_tests.len(), 1);
+    assert!(!record.shadow_tests[0].shadowed);
+    assert_color_eq!(record.color, Color::new(0.38066, 0.47583, 0.2855));
+    assert_color_eq!(record.surface.unwrap(), record.color);
+    assert_color_eq!(record.reflected.unwrap(), Color::BLACK);
+    assert!(record.children.is_empty());
+}
+
+#[test]
+fn record_a_miss() {
+    let w: World = Default::default();
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 1.0, 0.0));
+    let record = record_ray(&w, r, 3);
+    assert!(record.hit.is_none());
+    assert!(record.surface.is_none());
+    assert_color_eq!(record.color, Color::BLACK);
+    assert!(format!("{}", record).contains("miss"));
+}
+
+#[test]
+fn record_reflection_and_refraction() {
+    let w = world_with_glass_floor();
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -3.0),
+                     Vector4D::new_vector(0.0, -2.0_f64.sqrt() / 2.0, 2.0_f64.sqrt() / 2.0));
+    let record = record_ray(&w, r, 2);
+    let hit = record.hit.as_ref().unwrap();
+    assert_eq!(hit.object, "plane");
+    assert_f64_eq!(hit.n1, 1.0);
+    assert_f64_eq!(hit.n2, 1.5);
+    assert_eq!(record.children.len(), 2);
+    assert_eq!(record.children[0].kind, RayKind::Reflection);
+    assert_eq!(record.children[1].kind, RayKind::Refraction);
+    assert_eq!(record.children[0].remaining, 1);
+    // there's nothing below the floor
+    assert!(record.children[1].hit.is_none());
+    // children of children get their own kinds
+    assert!(record.children[0].children.iter().all(|c| c.kind == RayKind::Reflection || c.kind == RayKind::Refraction));
+    assert_color_eq!(record.color, color_at(&w, r, 2));
+}
+
+#[test]
+fn record_text_and_json() {
+    let w: World = Default::default();
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    let record = record_ray(&w, r, 3);
+    let text = format!("{}", record);
+    assert!(text.starts_with("Camera ray, 3 remaining, origin (0.00000, 0.00000, -5.00000)"));
+    assert!(text.contains("hit sphere at t 4.00000"));
+    assert!(text.contains("shadow test from"));
+    let json = record.to_json();
+    assert!(json.starts_with("{\"kind\": \"Camera\", \"remaining\": 3, \"origin\": [0, 0, -5]"));
+    assert!(json.contains("\"object\": \"sphere\""));
+    assert!(json.contains("\"shadowed\": false"));
+    assert!(json.ends_with("\"children\": []}"));
+}
+
+#[test]
+fn record_pixel_samples() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let records = record_pixel(&c, &w, &Default::default(), 5, 5);
+    assert_eq!(records.len(), 1);
+    assert_color_eq!(records[0].color, render_pixel(&c, &w, &Default::default(), 5, 5));
+    let config = RenderConfig {
+        samples_per_pixel: 4,
+        ..Default::default()
+    };
+    assert_eq!(record_pixel(&c, &w, &config, 5, 5).len(), 4);
+    assert!(record_pixel(&c, &w, &RenderConfig::new(Integrator::PathTracing), 5, 5).is_empty());
+    assert!(!recording_rays());
+}
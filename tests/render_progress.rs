@@ -0,0 +1,116 @@
+use utils::*;
+use types::*;
+extern crate raytracer_challenge_rs;
+use raytracer_challenge_rs::{Canvas, RenderConfig, CancelToken, render_with_progress};
+
+mod common;
+use common::default_camera;
+
+#[test]
+fn progress_after_every_row() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let config = RenderConfig {
+        samples_per_pixel: 2,
+        ..Default::default()
+    };
+    let mut updates = vec![];
+    let last = render_with_progress(&c, &w, &mut canvas, &config, &CancelToken::new(), |p| updates.push(*p));
+    assert_eq!(updates.len(), 11);
+    assert_eq!(updates[0].rows_done, 1);
+    assert_eq!(updates[0].total_rows, 11);
+    assert_eq!(updates[0].pixels_done, 11);
+    // the top row misses everything, there's just the two camera rays per pixel
+    assert_eq!(updates[0].rays_traced, 22);
+    assert!(updates.windows(2).all(|u| u[0].elapsed <= u[1].elapsed));
+    assert!(updates.windows(2).all(|u| u[0].rays_traced < u[1].rays_traced));
+    assert_eq!(last, updates[10]);
+    assert_eq!(last.pixels_done, 121);
+    // the pixels that hit the spheres add shadow rays
+    enable_stats();
+    render_with_progress(&c, &w, &mut canvas, &config, &CancelToken::new(), |_| ());
+    let stats = disable_stats();
+    assert!(last.rays_traced > 242);
+    assert_eq!(last.rays_traced, stats.world_intersections);
+    assert_eq!(last.eta.as_nanos(), 0);
+    assert_f64_eq!(last.fraction_done(), 1.0);
+    assert!(!last.cancelled);
+}
+
+#[test]
+fn cancel_keeps_rows_done() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let cancel = CancelToken::new();
+    let token = cancel.clone();
+    let last = render_with_progress(&c, &w, &mut canvas, &Default::default(), &token, |p| {
+        if p.rows_done == 6 {
+            cancel.cancel();
+        }
+    });
+    assert!(last.cancelled);
+    assert!(token.is_cancelled());
+    assert_eq!(last.rows_done, 6);
+    // the rows done are there and the rest is untouched
+    assert_color_eq!(canvas.get_pixel(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+    assert_color_eq!(canvas.get_pixel(5, 6), Color::BLACK);
+}
+
+// Cancels the render the first time it's shaded
+#[derive(Debug, Clone)]
+struct CancellingPattern {
+    cancel: CancelToken,
+    transform: Matrix4x4,
+}
+
+impl Patternable for CancellingPattern {
+    fn set_transform(&mut self, transform: Matrix4x4) {
+        self.transform = transform;
+    }
+
+    fn get_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+
+    fn pattern_at(&self, _point: Vector4D) -> Color {
+        self.cancel.cancel();
+        Color::WHITE
+    }
+}
+
+#[test]
+fn cancel_stops_in_the_middle_of_a_row() {
+    let mut w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let cancel = CancelToken::new();
+    let mut m = w.objects[0].get_material();
+    m.set_pattern(CancellingPattern { cancel: cancel.clone(), transform: Matrix4x4::new() });
+    w.objects[0].set_material(&m);
+    let mut calls = 0;
+    let last = render_with_progress(&c, &w, &mut canvas, &Default::default(), &cancel, |_| calls += 1);
+    assert!(last.cancelled);
+    // the first pixel that sees the sphere is rendered and the render stops right after it
+    assert_eq!(calls, last.rows_done);
+    assert!(last.pixels_done > last.rows_done * 11);
+    let (x, y) = ((last.pixels_done - 1) % 11, (last.pixels_done - 1) / 11);
+    assert!(canvas.get_pixel(x, y).red > 0.0);
+    assert_color_eq!(canvas.get_pixel(x + 1, y), Color::BLACK);
+}
+
+#[test]
+fn cancelled_before_start() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    let mut calls = 0;
+    let last = render_with_progress(&c, &w, &mut canvas, &Default::default(), &cancel, |_| calls += 1);
+    assert_eq!(calls, 0);
+    assert_eq!(last.rows_done, 0);
+    assert_eq!(last.pixels_done, 0);
+    assert!(last.cancelled);
+}
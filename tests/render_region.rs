@@ -0,0 +1,225 @@
+use utils::*;
+use types::*;
+extern crate raytracer_challenge_rs;
+use raytracer_challenge_rs::{Canvas, RenderConfig, RenderRegion, CancelToken, ProgressiveConfig,
+                             render_with_config, render_progressive, render_with_checkpoint};
+
+mod common;
+use common::default_camera;
+
+fn full_render() -> Canvas {
+    let mut canvas = Canvas::new(11, 11);
+    render_with_config(&default_camera(), &Default::default(), &mut canvas, &Default::default());
+    canvas
+}
+
+fn checkpoint_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("raytracer_checkpoint_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn region_intersection_and_tiles() {
+    let a = RenderRegion::new(2, 3, 5, 4);
+    assert_eq!(a.intersection(&RenderRegion::new(0, 0, 4, 4)), RenderRegion::new(2, 3, 2, 1));
+    assert!(a.intersection(&RenderRegion::new(8, 0, 4, 4)).is_empty());
+    assert!(a.contains(2, 3));
+    assert!(a.contains(6, 6));
+    assert!(!a.contains(7, 6));
+    let tiles = a.tiles(3);
+    assert_eq!(tiles, vec![RenderRegion::new(2, 3, 3, 3), RenderRegion::new(5, 3, 2, 3),
+                           RenderRegion::new(2, 6, 3, 1), RenderRegion::new(5, 6, 2, 1)]);
+    let config = RenderConfig {
+        region: Some(RenderRegion::new(8, 8, 10, 10)),
+        ..Default::default()
+    };
+    assert_eq!(config.pixels(&default_camera()), RenderRegion::new(8, 8, 3, 3));
+    assert_eq!(RenderConfig::default().pixels(&default_camera()), RenderRegion::new(0, 0, 11, 11));
+}
+
+#[test]
+fn render_only_the_region() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let full = full_render();
+    let mut canvas = Canvas::new(11, 11);
+    let marker = Color::new(0.0, 0.0, 1.0);
+    for y in 0..11 {
+        for x in 0..11 {
+            canvas.set_pixel(x, y, &marker);
+        }
+    }
+    let region = RenderRegion::new(4, 3, 3, 5);
+    let config = RenderConfig {
+        region: Some(region),
+        ..Default::default()
+    };
+    render_with_config(&c, &w, &mut canvas, &config);
+    for y in 0..11 {
+        for x in 0..11 {
+            let expected = if region.contains(x, y) { full.get_pixel(x, y) } else { marker };
+            assert_color_eq!(canvas.get_pixel(x, y), expected);
+        }
+    }
+}
+
+#[test]
+fn progressive_render_of_region() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let marker = Color::new(0.0, 0.0, 1.0);
+    canvas.set_pixel(0, 0, &marker);
+    let config = ProgressiveConfig {
+        render: RenderConfig {
+            region: Some(RenderRegion::new(4, 4, 3, 3)),
+            ..Default::default()
+        },
+        max_samples: Some(4),
+        ..Default::default()
+    };
+    let last = render_progressive(&c, &w, &mut canvas, &config, |_, _| ());
+    assert_color_eq!(canvas.get_pixel(0, 0), marker);
+    assert!(canvas.get_pixel(5, 5).red > 0.2);
+    assert!(last.noise.is_finite());
+}
+
+#[test]
+fn checkpoint_render_matches_full_render() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let path = checkpoint_path("full");
+    let mut canvas = Canvas::new(11, 11);
+    let finished = render_with_checkpoint(&c, &w, &mut canvas, &Default::default(), 4, &path, &CancelToken::new()).unwrap();
+    assert!(finished);
+    assert!(!path.exists());
+    let full = full_render();
+    for y in 0..11 {
+        for x in 0..11 {
+            assert_color_eq!(canvas.get_pixel(x, y), full.get_pixel(x, y));
+        }
+    }
+}
+
+#[test]
+fn checkpoint_render_with_stats() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let path = checkpoint_path("stats");
+    let mut canvas = Canvas::new(11, 11);
+    let config = RenderConfig {
+        stats: true,
+        ..Default::default()
+    };
+    let finished = render_with_checkpoint(&c, &w, &mut canvas, &config, 4, &path, &CancelToken::new()).unwrap();
+    assert!(finished);
+    assert!(!stats_enabled());
+    assert_color_eq!(canvas.get_pixel(5, 5), full_render().get_pixel(5, 5));
+}
+
+#[test]
+fn cancelled_checkpoint_render_keeps_checkpoint() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let path = checkpoint_path("cancelled");
+    let mut canvas = Canvas::new(11, 11);
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    let finished = render_with_checkpoint(&c, &w, &mut canvas, &Default::default(), 4, &path, &cancel).unwrap();
+    assert!(!finished);
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(contents.starts_with("checkpoint 11 11 0 0 11 11 4 Whitted 1 3 "), "{}", contents);
+    assert_eq!(contents.lines().count(), 1);
+    std::fs::remove_file(&path).unwrap();
+}
+
+// The header line a checkpoint render of w with config writes
+fn checkpoint_header(name: &str, w: &World, config: &RenderConfig) -> String {
+    let path = checkpoint_path(&format!("{}_header", name));
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    render_with_checkpoint(&default_camera(), w, &mut Canvas::new(11, 11), config, 4, &path, &cancel).unwrap();
+    let header = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    header
+}
+
+#[test]
+fn resume_from_checkpoint() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let path = checkpoint_path("resume");
+    // tile 1 is done, in a color the render wouldn't give so it's clear it isn't rendered again,
+    // and tile 0 was cut off
+    let row = ["1 0 0.5"; 4].join(" ");
+    let mut contents = checkpoint_header("resume", &w, &Default::default());
+    contents.push_str("tile 1\n");
+    for _ in 0..4 {
+        contents.push_str(&row);
+        contents.push('\n');
+    }
+    contents.push_str("end\ntile 0\n1 0 0.5 1 0");
+    std::fs::write(&path, contents).unwrap();
+
+    let mut canvas = Canvas::new(11, 11);
+    let finished = render_with_checkpoint(&c, &w, &mut canvas, &Default::default(), 4, &path, &CancelToken::new()).unwrap();
+    assert!(finished);
+    assert!(!path.exists());
+    let full = full_render();
+    assert_color_eq!(canvas.get_pixel(4, 0), Color::new(1.0, 0.0, 0.5));
+    assert_color_eq!(canvas.get_pixel(7, 3), Color::new(1.0, 0.0, 0.5));
+    assert_color_eq!(canvas.get_pixel(0, 0), full.get_pixel(0, 0));
+    assert_color_eq!(canvas.get_pixel(5, 5), full.get_pixel(5, 5));
+}
+
+#[test]
+fn checkpoint_for_another_render_is_an_error() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let path = checkpoint_path("mismatch");
+    std::fs::write(&path, "checkpoint 20 20 0 0 20 20 4\n").unwrap();
+    let mut canvas = Canvas::new(11, 11);
+    assert!(render_with_checkpoint(&c, &w, &mut canvas, &Default::default(), 4, &path, &CancelToken::new()).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn checkpoint_with_other_settings_is_an_error() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let path = checkpoint_path("settings");
+    let config = RenderConfig {
+        samples_per_pixel: 4,
+        ..Default::default()
+    };
+    std::fs::write(&path, checkpoint_header("settings", &w, &config)).unwrap();
+    let mut canvas = Canvas::new(11, 11);
+    assert!(render_with_checkpoint(&c, &w, &mut canvas, &Default::default(), 4, &path, &CancelToken::new()).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn checkpoint_of_edited_scene_is_an_error() {
+    let mut w: World = Default::default();
+    let c = default_camera();
+    let path = checkpoint_path("edited");
+    std::fs::write(&path, checkpoint_header("edited", &w, &Default::default())).unwrap();
+    w.light_source = LightSource::new(Color::WHITE, Vector4D::new_point(10.0, 10.0, -10.0));
+    let mut canvas = Canvas::new(11, 11);
+    assert!(render_with_checkpoint(&c, &w, &mut canvas, &Default::default(), 4, &path, &CancelToken::new()).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn cut_off_checkpoint_header_starts_over() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let path = checkpoint_path("cut_header");
+    std::fs::write(&path, "checkpoint 11 11 0").unwrap();
+    let mut canvas = Canvas::new(11, 11);
+    let finished = render_with_checkpoint(&c, &w, &mut canvas, &Default::default(), 4, &path, &CancelToken::new()).unwrap();
+    assert!(finished);
+    assert!(!path.exists());
+    assert_color_eq!(canvas.get_pixel(5, 5), full_render().get_pixel(5, 5));
+}
@@ -0,0 +1,154 @@
+use utils::*;
+use types::*;
+extern crate raytracer_challenge_rs;
+use raytracer_challenge_rs::{Canvas, RenderConfig, CancelToken, SampleBuffer, render_with_config, render_with_stats, render_with_progress, heat_color};
+
+mod common;
+use common::default_camera;
+
+#[test]
+fn counting_is_off_by_default() {
+    assert!(!stats_enabled());
+    let w: World = Default::default();
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    color_at(&w, r, 3);
+    assert_eq!(take_stats(), RayStats::default());
+}
+
+#[test]
+fn count_rays_and_shape_tests() {
+    let w: World = Default::default();
+    enable_stats();
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    color_at(&w, r, 3);
+    let stats = disable_stats();
+    assert!(!stats_enabled());
+    // the camera ray and the shadow ray, each tested against both spheres
+    assert_eq!(stats.world_intersections, 2);
+    assert_eq!(stats.shadow_rays, 1);
+    assert_eq!(stats.shape_tests.get("sphere"), Some(&4));
+    assert_eq!(stats.total_shape_tests(), 4);
+    assert_eq!(stats.reflection_rays, 0);
+    assert_eq!(stats.refraction_rays, 0);
+}
+
+#[test]
+fn count_reflection_and_refraction() {
+    let mut w: World = Default::default();
+    let mut floor = Plane::new();
+    floor.set_transform(Matrix4x4::translation(0.0, -1.0, 0.0));
+    let mut m = floor.get_material();
+    m.reflective = 0.5;
+    m.transparency = 0.5;
+    m.refractive_index = 1.5;
+    floor.set_material(m);
+    w.objects.push(Shape::Plane(floor));
+    enable_stats();
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -3.0),
+                     Vector4D::new_vector(0.0, -2.0_f64.sqrt() / 2.0, 2.0_f64.sqrt() / 2.0));
+    color_at(&w, r, 1);
+    let stats = disable_stats();
+    assert_eq!(stats.reflection_rays, 1);
+    assert_eq!(stats.refraction_rays, 1);
+    assert!(stats.shape_tests.contains_key("plane"));
+}
+
+#[test]
+fn render_stats_per_pixel() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let stats = render_with_stats(&c, &w, &mut canvas, &Default::default());
+    assert!(!stats_enabled());
+    // the image is the same as without counting
+    let mut expected = Canvas::new(11, 11);
+    render_with_config(&c, &w, &mut expected, &Default::default());
+    assert_color_eq!(canvas.get_pixel(5, 5), expected.get_pixel(5, 5));
+    // a miss is just the camera ray, a hit adds the shadow ray
+    assert_eq!(stats.rays[0], 1);
+    assert_eq!(stats.shape_tests[0], 2);
+    assert_eq!(stats.rays[5 * 11 + 5], 2);
+    assert_eq!(stats.shape_tests[5 * 11 + 5], 4);
+    assert_eq!(stats.total.world_intersections, stats.rays.iter().sum::<usize>());
+    assert_eq!(stats.total.shadow_rays, stats.rays.iter().filter(|r| **r == 2).count());
+    let summary = format!("{}", stats);
+    assert!(summary.contains("11x11 pixels"));
+    assert!(summary.contains("sphere: "));
+    assert!(summary.contains("shadow rays: "));
+}
+
+#[test]
+fn heatmaps() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let stats = render_with_stats(&c, &w, &mut canvas, &RenderConfig::default());
+    let rays = stats.rays_heatmap();
+    assert_color_eq!(rays.get_pixel(5, 5), Color::WHITE);
+    assert_color_eq!(rays.get_pixel(0, 0), heat_color(0.5));
+    assert_color_eq!(stats.shape_tests_heatmap().get_pixel(5, 5), Color::WHITE);
+    assert_color_eq!(heat_color(0.0), Color::BLACK);
+    assert_color_eq!(heat_color(0.5), Color::new(1.0, 0.5, 0.0));
+    assert_color_eq!(heat_color(2.0), Color::WHITE);
+}
+
+#[test]
+fn stats_option_counts_during_render_with_progress() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let config = RenderConfig {
+        stats: true,
+        ..Default::default()
+    };
+    // counting that was already on carries on, with the render's rays added
+    enable_stats();
+    let r = Ray::new(Vector4D::new_point(0.0, 0.0, -5.0), Vector4D::new_vector(0.0, 0.0, 1.0));
+    color_at(&w, r, 3);
+    let last = render_with_progress(&c, &w, &mut canvas, &config, &CancelToken::new(), |_| ());
+    assert!(stats_enabled());
+    let stats = disable_stats();
+    assert_eq!(stats.world_intersections, 2 + last.rays_traced);
+    let stats = render_with_stats(&c, &w, &mut canvas, &config);
+    assert_eq!(stats.total.world_intersections, last.rays_traced);
+}
+
+#[test]
+fn stats_option_stops_counting_when_cancelled() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut canvas = Canvas::new(11, 11);
+    let config = RenderConfig {
+        stats: true,
+        ..Default::default()
+    };
+    let cancel = CancelToken::new();
+    let token = cancel.clone();
+    let last = render_with_progress(&c, &w, &mut canvas, &config, &token, |p| {
+        if p.rows_done == 3 {
+            cancel.cancel();
+        }
+    });
+    assert!(last.cancelled);
+    assert!(!stats_enabled());
+}
+
+#[test]
+fn stats_option_counts_progressive_passes() {
+    let w: World = Default::default();
+    let c = default_camera();
+    let mut buffer = SampleBuffer::new(11, 11);
+    buffer.render_pass(&c, &w, &Default::default());
+    assert!(buffer.stats.is_none());
+    let config = RenderConfig {
+        stats: true,
+        ..Default::default()
+    };
+    buffer.render_pass(&c, &w, &config);
+    buffer.render_pass(&c, &w, &config);
+    assert!(!stats_enabled());
+    let stats = buffer.stats.unwrap();
+    // two passes of a sample each
+    assert_eq!(stats.rays[0], 2);
+    assert_eq!(stats.rays[5 * 11 + 5], 4);
+}
@@ -0,0 +1,280 @@
+use utils::*;
+use types::*;
+extern crate raytracer_challenge_rs;
+use raytracer_challenge_rs::{Canvas, ImageTexture, TextureFilter, TextureWrap};
+use std::f64::consts::FRAC_1_SQRT_2;
+
+#[test]
+fn test_uv_checkers_pattern() {
+    let checkers = UvCheckersPattern::new(2.0, 2.0, Color::BLACK, Color::white());
+    assert_color_eq!(checkers.uv_pattern_at(0.0, 0.0), Color::BLACK);
+    assert_color_eq!(checkers.uv_pattern_at(0.5, 0.0), Color::white());
+    assert_color_eq!(checkers.uv_pattern_at(0.0, 0.5), Color::white());
+    assert_color_eq!(checkers.uv_pattern_at(0.5, 0.5), Color::BLACK);
+    assert_color_eq!(checkers.uv_pattern_at(1.0, 1.0), Color::BLACK);
+}
+
+#[test]
+fn test_spherical_map() {
+    let cases = [
+        (Vector4D::new_point(0.0, 0.0, -1.0), 0.0, 0.5),
+        (Vector4D::new_point(1.0, 0.0, 0.0), 0.25, 0.5),
+        (Vector4D::new_point(0.0, 0.0, 1.0), 0.5, 0.5),
+        (Vector4D::new_point(-1.0, 0.0, 0.0), 0.75, 0.5),
+        (Vector4D::new_point(0.0, 1.0, 0.0), 0.5, 1.0),
+        (Vector4D::new_point(0.0, -1.0, 0.0), 0.5, 0.0),
+        (Vector4D::new_point(2.0f64.sqrt()/2.0, 2.0f64.sqrt()/2.0, 0.0), 0.25, 0.75),
+    ];
+    for (p, u, v) in cases.iter() {
+        let (pu, pv) = spherical_map(*p);
+        assert_f64_eq!(pu, *u);
+        assert_f64_eq!(pv, *v);
+    }
+}
+
+#[test]
+fn test_spherical_map_of_center() {
+    let (u, v) = spherical_map(Vector4D::new_point(0.0, 0.0, 0.0));
+    assert_f64_eq!(u, 0.5);
+    assert_f64_eq!(v, 0.5);
+}
+
+#[test]
+fn test_texture_map_pattern_spherical() {
+    let checkers = UvCheckersPattern::new(16.0, 8.0, Color::BLACK, Color::white());
+    let pattern = TextureMapPattern::new(checkers, UvMapping::Spherical);
+    let cases = [
+        (Vector4D::new_point(0.4315, 0.4670, 0.7719), Color::white()),
+        (Vector4D::new_point(-0.9654, 0.2552, -0.0534), Color::BLACK),
+        (Vector4D::new_point(0.1039, 0.7090, 0.6975), Color::white()),
+        (Vector4D::new_point(-0.4986, -0.7856, -0.3663), Color::BLACK),
+        (Vector4D::new_point(-0.0317, -0.9395, 0.3411), Color::BLACK),
+        (Vector4D::new_point(0.4809, -0.7721, 0.4154), Color::BLACK),
+        (Vector4D::new_point(0.0285, -0.9612, -0.2745), Color::BLACK),
+        (Vector4D::new_point(-0.5734, -0.2162, -0.7903), Color::white()),
+        (Vector4D::new_point(0.7688, -0.1470, 0.6223), Color::BLACK),
+        (Vector4D::new_point(-0.7652, 0.2175, 0.6060), Color::BLACK),
+    ];
+    for (p, c) in cases.iter() {
+        assert_color_eq!(pattern.pattern_at(*p), *c);
+    }
+}
+
+#[test]
+fn test_planar_map() {
+    let cases = [
+        (Vector4D::new_point(0.25, 0.0, 0.5), 0.25, 0.5),
+        (Vector4D::new_point(0.25, 0.0, -0.25), 0.25, 0.75),
+        (Vector4D::new_point(0.25, 0.5, -0.25), 0.25, 0.75),
+        (Vector4D::new_point(1.25, 0.0, 0.5), 0.25, 0.5),
+        (Vector4D::new_point(0.25, 0.0, -1.75), 0.25, 0.25),
+        (Vector4D::new_point(1.0, 0.0, -1.0), 0.0, 0.0),
+        (Vector4D::new_point(0.0, 0.0, 0.0), 0.0, 0.0),
+    ];
+    for (p, u, v) in cases.iter() {
+        let (pu, pv) = planar_map(*p);
+        assert_f64_eq!(pu, *u);
+        assert_f64_eq!(pv, *v);
+    }
+}
+
+#[test]
+fn test_cylindrical_map() {
+    let cases = [
+        (Vector4D::new_point(0.0, 0.0, -1.0), 0.0, 0.0),
+        (Vector4D::new_point(0.0, 0.5, -1.0), 0.0, 0.5),
+        (Vector4D::new_point(0.0, 1.0, -1.0), 0.0, 0.0),
+        (Vector4D::new_point(FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2), 0.125, 0.5),
+        (Vector4D::new_point(1.0, 0.5, 0.0), 0.25, 0.5),
+        (Vector4D::new_point(FRAC_1_SQRT_2, 0.5, FRAC_1_SQRT_2), 0.375, 0.5),
+        (Vector4D::new_point(0.0, -0.25, 1.0), 0.5, 0.75),
+        (Vector4D::new_point(-FRAC_1_SQRT_2, 0.5, FRAC_1_SQRT_2), 0.625, 0.5),
+        (Vector4D::new_point(-1.0, 1.25, 0.0), 0.75, 0.25),
+        (Vector4D::new_point(-FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2), 0.875, 0.5),
+    ];
+    for (p, u, v) in cases.iter() {
+        let (pu, pv) = cylindrical_map(*p);
+        assert_f64_eq!(pu, *u);
+        assert_f64_eq!(pv, *v);
+    }
+}
+
+#[test]
+fn test_uv_align_check_pattern() {
+    let main = Color::white();
+    let ul = Color::red();
+    let ur = Color::new(1.0, 1.0, 0.0);
+    let bl = Color::green();
+    let br = Color::new(0.0, 1.0, 1.0);
+    let pattern = UvAlignCheckPattern::new(main, ul, ur, bl, br);
+    assert_color_eq!(pattern.uv_pattern_at(0.5, 0.5), main);
+    assert_color_eq!(pattern.uv_pattern_at(0.1, 0.9), ul);
+    assert_color_eq!(pattern.uv_pattern_at(0.9, 0.9), ur);
+    assert_color_eq!(pattern.uv_pattern_at(0.1, 0.1), bl);
+    assert_color_eq!(pattern.uv_pattern_at(0.9, 0.1), br);
+}
+
+#[test]
+fn test_cube_face_from_point() {
+    assert_eq!(cube_face_from_point(Vector4D::new_point(-1.0, 0.5, -0.25)), CubeFace::Left);
+    assert_eq!(cube_face_from_point(Vector4D::new_point(1.1, -0.75, 0.8)), CubeFace::Right);
+    assert_eq!(cube_face_from_point(Vector4D::new_point(0.1, 0.6, 0.9)), CubeFace::Front);
+    assert_eq!(cube_face_from_point(Vector4D::new_point(-0.7, 0.0, -2.0)), CubeFace::Back);
+    assert_eq!(cube_face_from_point(Vector4D::new_point(0.5, 1.0, 0.9)), CubeFace::Up);
+    assert_eq!(cube_face_from_point(Vector4D::new_point(-0.2, -1.3, 1.1)), CubeFace::Down);
+}
+
+#[test]
+fn test_cube_face_uv() {
+    let cases = [
+        (CubeFace::Front, Vector4D::new_point(-0.5, 0.5, 1.0), 0.25, 0.75),
+        (CubeFace::Front, Vector4D::new_point(0.5, -0.5, 1.0), 0.75, 0.25),
+        (CubeFace::Back, Vector4D::new_point(0.5, 0.5, -1.0), 0.25, 0.75),
+        (CubeFace::Back, Vector4D::new_point(-0.5, -0.5, -1.0), 0.75, 0.25),
+        (CubeFace::Left, Vector4D::new_point(-1.0, 0.5, -0.5), 0.25, 0.75),
+        (CubeFace::Left, Vector4D::new_point(-1.0, -0.5, 0.5), 0.75, 0.25),
+        (CubeFace::Right, Vector4D::new_point(1.0, 0.5, 0.5), 0.25, 0.75),
+        (CubeFace::Right, Vector4D::new_point(1.0, -0.5, -0.5), 0.75, 0.25),
+        (CubeFace::Up, Vector4D::new_point(-0.5, 1.0, -0.5), 0.25, 0.75),
+        (CubeFace::Up, Vector4D::new_point(0.5, 1.0, 0.5), 0.75, 0.25),
+        (CubeFace::Down, Vector4D::new_point(-0.5, -1.0, 0.5), 0.25, 0.75),
+        (CubeFace::Down, Vector4D::new_point(0.5, -1.0, -0.5), 0.75, 0.25),
+    ];
+    for (face, p, u, v) in cases.iter() {
+        let (pu, pv) = cube_face_uv(*face, *p);
+        assert_f64_eq!(pu, *u);
+        assert_f64_eq!(pv, *v);
+    }
+}
+
+#[test]
+fn test_cube_map_pattern() {
+    let red = Color::red();
+    let yellow = Color::new(1.0, 1.0, 0.0);
+    let brown = Color::new(1.0, 0.5, 0.0);
+    let green = Color::green();
+    let cyan = Color::new(0.0, 1.0, 1.0);
+    let blue = Color::blue();
+    let purple = Color::new(1.0, 0.0, 1.0);
+    let white = Color::white();
+    let left = UvAlignCheckPattern::new(yellow, cyan, red, blue, brown);
+    let front = UvAlignCheckPattern::new(cyan, red, yellow, brown, green);
+    let right = UvAlignCheckPattern::new(red, yellow, purple, green, white);
+    let back = UvAlignCheckPattern::new(green, purple, cyan, white, blue);
+    let up = UvAlignCheckPattern::new(brown, cyan, purple, red, yellow);
+    let down = UvAlignCheckPattern::new(purple, brown, green, blue, white);
+    let pattern = CubeMapPattern::new(Box::new(left), Box::new(front), Box::new(right),
+                                      Box::new(back), Box::new(up), Box::new(down));
+    let cases = [
+        (Vector4D::new_point(-1.0, 0.0, 0.0), yellow),
+        (Vector4D::new_point(-1.0, 0.9, -0.9), cyan),
+        (Vector4D::new_point(-1.0, 0.9, 0.9), red),
+        (Vector4D::new_point(-1.0, -0.9, -0.9), blue),
+        (Vector4D::new_point(-1.0, -0.9, 0.9), brown),
+        (Vector4D::new_point(0.0, 0.0, 1.0), cyan),
+        (Vector4D::new_point(-0.9, 0.9, 1.0), red),
+        (Vector4D::new_point(0.9, 0.9, 1.0), yellow),
+        (Vector4D::new_point(-0.9, -0.9, 1.0), brown),
+        (Vector4D::new_point(0.9, -0.9, 1.0), green),
+        (Vector4D::new_point(1.0, 0.0, 0.0), red),
+        (Vector4D::new_point(1.0, 0.9, 0.9), yellow),
+        (Vector4D::new_point(1.0, 0.9, -0.9), purple),
+        (Vector4D::new_point(1.0, -0.9, 0.9), green),
+        (Vector4D::new_point(1.0, -0.9, -0.9), white),
+        (Vector4D::new_point(0.0, 0.0, -1.0), green),
+        (Vector4D::new_point(0.9, 0.9, -1.0), purple),
+        (Vector4D::new_point(-0.9, 0.9, -1.0), cyan),
+        (Vector4D::new_point(0.9, -0.9, -1.0), white),
+        (Vector4D::new_point(-0.9, -0.9, -1.0), blue),
+        (Vector4D::new_point(0.0, 1.0, 0.0), brown),
+        (Vector4D::new_point(-0.9, 1.0, -0.9), cyan),
+        (Vector4D::new_point(0.9, 1.0, -0.9), purple),
+        (Vector4D::new_point(-0.9, 1.0, 0.9), red),
+        (Vector4D::new_point(0.9, 1.0, 0.9), yellow),
+        (Vector4D::new_point(0.0, -1.0, 0.0), purple),
+        (Vector4D::new_point(-0.9, -1.0, 0.9), brown),
+        (Vector4D::new_point(0.9, -1.0, 0.9), green),
+        (Vector4D::new_point(-0.9, -1.0, -0.9), blue),
+        (Vector4D::new_point(0.9, -1.0, -0.9), white),
+    ];
+    for (p, c) in cases.iter() {
+        assert_color_eq!(pattern.pattern_at(*p), *c);
+    }
+}
+
+#[test]
+fn test_texture_map_on_plane_material() {
+    let mut plane = Plane::new();
+    let mut m: Material = Default::default();
+    m.set_pattern(TextureMapPattern::new(UvCheckersPattern::new(2.0, 2.0, Color::BLACK, Color::white()), UvMapping::Planar));
+    plane.set_material(m);
+    let shape = Shape::Plane(plane);
+    let pattern = shape.get_material().pattern.unwrap();
+    assert_color_eq!(pattern.pattern_at_object(shape.as_intersectable(), Vector4D::new_point(0.25, 0.0, 0.25)), Color::BLACK);
+    assert_color_eq!(pattern.pattern_at_object(shape.as_intersectable(), Vector4D::new_point(0.75, 0.0, 0.25)), Color::white());
+}
+
+fn checker_canvas() -> Canvas {
+    // 2x2 image, top row red green, bottom row blue white
+    Canvas::from_ppm_str("P3\n2 2\n255\n255 0 0 0 255 0\n0 0 255 255 255 255\n").unwrap()
+}
+
+#[test]
+fn test_image_texture_nearest() {
+    let mut texture = ImageTexture::new(checker_canvas());
+    texture.filter = TextureFilter::Nearest;
+    assert_color_eq!(texture.uv_pattern_at(0.25, 0.75), Color::red());
+    assert_color_eq!(texture.uv_pattern_at(0.75, 0.75), Color::green());
+    assert_color_eq!(texture.uv_pattern_at(0.25, 0.25), Color::blue());
+    assert_color_eq!(texture.uv_pattern_at(0.75, 0.25), Color::white());
+}
+
+#[test]
+fn test_image_texture_bilinear_and_wrap() {
+    let mut texture = ImageTexture::new(checker_canvas());
+    // The center of the image is the average of all 4 texels
+    assert_color_eq!(texture.uv_pattern_at(0.5, 0.5), Color::new(0.5, 0.5, 0.5));
+    // Halfway between red and green along the top row
+    assert_color_eq!(texture.uv_pattern_at(0.5, 0.75), Color::new(0.5, 0.5, 0.0));
+    // Repeat blends the left edge with the right edge
+    assert_color_eq!(texture.uv_pattern_at(0.0, 0.75), Color::new(0.5, 0.5, 0.0));
+    texture.wrap = TextureWrap::Clamp;
+    assert_color_eq!(texture.uv_pattern_at(0.0, 0.75), Color::red());
+}
+
+#[test]
+fn test_image_texture_mipmaps() {
+    let mut texture = ImageTexture::new(checker_canvas());
+    texture.filter = TextureFilter::Nearest;
+    texture.generate_mipmaps();
+    assert_eq!(texture.mip_levels.len(), 2);
+    assert_eq!(texture.mip_levels[1].width, 1);
+    texture.mip_level = 1.0;
+    assert_color_eq!(texture.uv_pattern_at(0.25, 0.75), Color::new(0.5, 0.5, 0.5));
+    texture.mip_level = 0.5;
+    assert_color_eq!(texture.uv_pattern_at(0.25, 0.75), Color::new(0.75, 0.25, 0.25));
+}
+
+#[test]
+fn test_image_texture_level_for_footprint() {
+    let mut texture = ImageTexture::new(checker_canvas());
+    texture.generate_mipmaps();
+    // a pixel covering a texel or less uses the full image
+    assert_f64_eq!(texture.level_for_footprint(0.25), 0.0);
+    assert_f64_eq!(texture.level_for_footprint(0.5), 0.0);
+    assert_f64_eq!(texture.level_for_footprint(0.75), 1.5f64.log2());
+    // and it stops at the smallest level
+    assert_f64_eq!(texture.level_for_footprint(10.0), 1.0);
+}
+
+#[test]
+fn test_image_texture_on_sphere() {
+    let mut texture = ImageTexture::new(checker_canvas());
+    texture.wrap = TextureWrap::Clamp;
+    let mut m: Material = Default::default();
+    m.set_pattern(TextureMapPattern::new(texture, UvMapping::Spherical));
+    let s = Sphere::new();
+    // The north pole is the middle of the top row of the image
+    let c = m.pattern.unwrap().pattern_at_object(&s, Vector4D::new_point(0.0, 1.0, 0.0));
+    assert_color_eq!(c, Color::new(0.5, 0.5, 0.0));
+}